@@ -0,0 +1,244 @@
+use bitcoin::{absolute, transaction, Amount, Psbt, Transaction, TxIn, TxOut};
+use serde::Deserialize;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    add_funding_input, add_labitbu_leaf, check_dust, create_taproot_spend_info,
+    first_sat_locations, parse_mainnet_address, Deposit, Error, FundingInput, PsbtResult,
+    SatLocation,
+};
+
+/// A deposit to mint in a batch, and the address its labitbu is sent to.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BatchDeposit {
+    pub deposit: Deposit,
+    pub destination_address: String,
+}
+
+/// Builds one mint transaction spending every deposit.
+///
+/// Deposits are sorted by outpoint so input `i` always pays output `i`, no
+/// matter what order the caller supplied them in. Every output but the last
+/// carries exactly its deposit, so each labitbu sat is the first sat of its
+/// output however many labitbus are minted.
+///
+/// Without `funding` the whole fee comes out of the last output. With it, the
+/// funding inputs follow the deposits and pay the fee, the last output keeps
+/// its full deposit too, and any change goes to the first funding input's
+/// script as an extra output.
+pub fn build_batch_mint(
+    mut deposits: Vec<BatchDeposit>,
+    funding: Vec<FundingInput>,
+    fee: Amount,
+) -> Result<Psbt, Error> {
+    if deposits.is_empty() {
+        return Err(Error::NoDeposits);
+    }
+    deposits.sort_by_key(|d| d.deposit.outpoint);
+    let count = deposits.len();
+
+    let mut inputs = Vec::with_capacity(deposits.len());
+    let mut outputs = Vec::with_capacity(deposits.len() + 1);
+    for d in &deposits {
+        let destination = parse_mainnet_address(&d.destination_address)?;
+        inputs.push(TxIn {
+            previous_output: d.deposit.outpoint,
+            ..Default::default()
+        });
+        outputs.push(TxOut {
+            value: d.deposit.prevout.value,
+            script_pubkey: destination.script_pubkey(),
+        });
+    }
+
+    if let Some(first) = funding.first() {
+        let funds: Amount = funding.iter().map(|f| f.prevout.value).sum();
+        let change = funds.checked_sub(fee).ok_or(Error::InsufficientFunds {
+            available: funds,
+            required: fee,
+        })?;
+        if change > Amount::ZERO {
+            outputs.push(TxOut {
+                value: change,
+                script_pubkey: first.prevout.script_pubkey.clone(),
+            });
+        }
+    } else {
+        let last = outputs.last_mut().expect("at least one deposit");
+        last.value = last
+            .value
+            .checked_sub(fee)
+            .ok_or(Error::InsufficientFunds {
+                available: last.value,
+                required: fee,
+            })?;
+    }
+
+    for (vout, output) in outputs.iter().enumerate() {
+        check_dust(vout, output)?;
+    }
+
+    let unsigned_tx = Transaction {
+        version: transaction::Version(2),
+        lock_time: absolute::LockTime::ZERO,
+        input: inputs,
+        output: outputs,
+    };
+    let mut psbt = Psbt::from_unsigned_tx(unsigned_tx)?;
+
+    for (psbt_in, d) in psbt.inputs.iter_mut().zip(deposits) {
+        let taproot_spend_info = create_taproot_spend_info(d.deposit.pubkey, d.deposit.payload)?;
+        psbt_in.witness_utxo = Some(d.deposit.prevout);
+        add_labitbu_leaf(psbt_in, d.deposit.pubkey, &taproot_spend_info);
+    }
+    for f in funding {
+        add_funding_input(&mut psbt, f)?;
+    }
+
+    // Each labitbu sat must land in its own output, never a neighbour's.
+    for (input, location) in first_sat_locations(&psbt)?
+        .into_iter()
+        .enumerate()
+        .take(count)
+    {
+        if !matches!(location, SatLocation::Output { vout, .. } if vout == input) {
+            return Err(Error::SatNotPreserved { input });
+        }
+    }
+
+    Ok(psbt)
+}
+
+#[wasm_bindgen]
+pub fn mint_batch(deposits: JsValue, funding: JsValue, fee: u64) -> Result<PsbtResult, JsValue> {
+    let deposits: Vec<BatchDeposit> = serde_wasm_bindgen::from_value(deposits)
+        .map_err(|e| JsValue::from_str(&format!("deposits: {}", e)))?;
+    let funding: Vec<FundingInput> = if funding.is_undefined() || funding.is_null() {
+        Vec::new()
+    } else {
+        serde_wasm_bindgen::from_value(funding)
+            .map_err(|e| JsValue::from_str(&format!("funding: {}", e)))?
+    };
+
+    let psbt = build_batch_mint(deposits, funding, Amount::from_sat(fee))
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        hashes::Hash, key::TweakedPublicKey, Address, Network, OutPoint, Txid, XOnlyPublicKey,
+    };
+
+    use crate::{nums_from_tag, spend_script};
+
+    fn destination() -> String {
+        let key = TweakedPublicKey::dangerous_assume_tweaked(nums_from_tag(b"destination"));
+        Address::p2tr_tweaked(key, Network::Bitcoin).to_string()
+    }
+
+    fn deposit(tag: &[u8], vout: u32, value: u64) -> BatchDeposit {
+        let pubkey = nums_from_tag(tag);
+        let payload = vec![tag[0]; 64];
+        let spend_info = create_taproot_spend_info(pubkey, payload.clone()).unwrap();
+        BatchDeposit {
+            deposit: Deposit {
+                pubkey,
+                payload,
+                outpoint: OutPoint::new(Txid::all_zeros(), vout),
+                prevout: TxOut {
+                    value: Amount::from_sat(value),
+                    script_pubkey: Address::p2tr_tweaked(spend_info.output_key(), Network::Bitcoin)
+                        .script_pubkey(),
+                },
+            },
+            destination_address: destination(),
+        }
+    }
+
+    fn leaf_key(psbt_in: &bitcoin::psbt::Input) -> XOnlyPublicKey {
+        let (script, _) = psbt_in.tap_scripts.values().next().unwrap();
+        XOnlyPublicKey::from_slice(&script.as_bytes()[1..33]).unwrap()
+    }
+
+    #[test]
+    fn batch_mint_orders_inputs_and_gives_each_its_own_output() {
+        let deposits = vec![deposit(b"b", 1, 10_000), deposit(b"a", 0, 12_000)];
+
+        let psbt = build_batch_mint(deposits, vec![], Amount::from_sat(501)).unwrap();
+
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input[0].previous_output.vout, 0);
+        assert_eq!(tx.input[1].previous_output.vout, 1);
+        assert_eq!(tx.output[0].value, Amount::from_sat(12_000));
+        assert_eq!(tx.output[1].value, Amount::from_sat(10_000 - 501));
+
+        assert_eq!(leaf_key(&psbt.inputs[0]), nums_from_tag(b"a"));
+        assert_eq!(leaf_key(&psbt.inputs[1]), nums_from_tag(b"b"));
+        for psbt_in in &psbt.inputs {
+            let (ctrl_block, (script, _)) = psbt_in.tap_scripts.iter().next().unwrap();
+            let output_key = psbt_in
+                .witness_utxo
+                .as_ref()
+                .unwrap()
+                .script_pubkey
+                .as_bytes()[2..]
+                .to_vec();
+            assert!(ctrl_block.verify_taproot_commitment(
+                &secp256k1::Secp256k1::verification_only(),
+                XOnlyPublicKey::from_slice(&output_key).unwrap(),
+                script
+            ));
+            assert_eq!(*script, spend_script(leaf_key(psbt_in)));
+        }
+    }
+
+    #[test]
+    fn batch_mint_rejects_fee_larger_than_deposit() {
+        let deposits = vec![deposit(b"a", 0, 100)];
+
+        assert!(matches!(
+            build_batch_mint(deposits.clone(), vec![], Amount::from_sat(101)),
+            Err(Error::InsufficientFunds { .. })
+        ));
+        assert!(matches!(
+            build_batch_mint(deposits, vec![], Amount::from_sat(1)),
+            Err(Error::DustOutput { vout: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn large_batch_keeps_every_sat_with_funding_paying_the_fee() {
+        let deposits: Vec<_> = (0..40u8)
+            .map(|i| deposit(&[b'a' + i % 26, i], u32::from(i), 10_000))
+            .collect();
+        let pubkey = secp256k1::PublicKey::from_secret_key(
+            &secp256k1::Secp256k1::new(),
+            &secp256k1::SecretKey::from_slice(&[7u8; 32]).unwrap(),
+        );
+        let wallet = FundingInput {
+            outpoint: OutPoint::new(Txid::all_zeros(), 100),
+            prevout: TxOut {
+                value: Amount::from_sat(100_000),
+                script_pubkey: bitcoin::ScriptBuf::new_p2wpkh(
+                    &bitcoin::CompressedPublicKey(pubkey).wpubkey_hash(),
+                ),
+            },
+            kind: crate::FundingKind::P2wpkh { pubkey },
+        };
+
+        let psbt = build_batch_mint(deposits, vec![wallet], Amount::from_sat(44_000)).unwrap();
+
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 41);
+        assert_eq!(tx.output.len(), 41);
+        assert!(tx.output[..40]
+            .iter()
+            .all(|o| o.value == Amount::from_sat(10_000)));
+        assert_eq!(tx.output[40].value, Amount::from_sat(56_000));
+        assert_eq!(psbt.fee().unwrap(), Amount::from_sat(44_000));
+    }
+}
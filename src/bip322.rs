@@ -0,0 +1,497 @@
+use std::str::FromStr;
+
+use bitcoin::{
+    absolute,
+    base64::{engine::general_purpose::STANDARD, Engine},
+    consensus::{deserialize, serialize},
+    hashes::{sha256, Hash, HashEngine},
+    opcodes::{all::OP_RETURN, OP_0},
+    script::Builder,
+    transaction, Address, Amount, Network, OutPoint, Psbt, Script, ScriptBuf, Sequence,
+    Transaction, TxIn, TxOut, Txid, Witness,
+};
+use secp256k1::Secp256k1;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    add_labitbu_leaf, create_taproot_spend_info, finalize_labitbu_input, sign_psbt_with,
+    verify_taproot_spends, EphemeralKey, Error, Signer,
+};
+
+/// How a BIP322 signature is encoded: just the witness of the signing
+/// transaction, or the whole transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bip322Format {
+    Simple,
+    Full,
+}
+
+fn message_hash(message: &[u8]) -> sha256::Hash {
+    let tag = sha256::Hash::hash(b"BIP0322-signed-message");
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(message);
+    sha256::Hash::from_engine(engine)
+}
+
+/// The virtual transaction paying the message challenge, which the proof
+/// then spends.
+fn to_spend(challenge: &Script, message: &[u8]) -> Transaction {
+    Transaction {
+        version: transaction::Version(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(Txid::all_zeros(), 0xFFFFFFFF),
+            script_sig: Builder::new()
+                .push_opcode(OP_0)
+                .push_slice(message_hash(message).to_byte_array())
+                .into_script(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: challenge.to_owned(),
+        }],
+    }
+}
+
+/// The unsigned transaction whose witness is the proof.
+fn to_sign(to_spend: &Transaction) -> Transaction {
+    Transaction {
+        version: transaction::Version(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(to_spend.compute_txid(), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+fn encode(to_sign: &Transaction, format: Bip322Format) -> String {
+    let bytes = match format {
+        Bip322Format::Simple => serialize(&to_sign.input[0].witness),
+        Bip322Format::Full => serialize(to_sign),
+    };
+    STANDARD.encode(bytes)
+}
+
+/// Signs `message` for the BIP86 key path address of `signer`'s key, the
+/// kind of address a labitbu is usually held at.
+pub fn sign_message_key_path(
+    signer: &dyn Signer,
+    message: &str,
+    format: Bip322Format,
+) -> Result<String, Error> {
+    let pubkey = signer.get_xonly_pubkey()?;
+    let challenge = ScriptBuf::new_p2tr(&Secp256k1::verification_only(), pubkey, None);
+    let to_spend = to_spend(&challenge, message.as_bytes());
+
+    let mut psbt = Psbt::from_unsigned_tx(to_sign(&to_spend))?;
+    psbt.inputs[0].witness_utxo = Some(to_spend.output[0].clone());
+    psbt.inputs[0].tap_internal_key = Some(pubkey);
+    if sign_psbt_with(&mut psbt, signer)? != 1 {
+        return Err(Error::MissingSignature(0));
+    }
+    let signature = psbt.inputs[0]
+        .tap_key_sig
+        .ok_or(Error::MissingSignature(0))?;
+    let mut to_sign = psbt.unsigned_tx;
+    to_sign.input[0].witness = Witness::from_slice(&[signature.to_vec()]);
+
+    Ok(encode(&to_sign, format))
+}
+
+/// Signs `message` for the deposit address of `signer`'s key and
+/// `payload_bytes`, spending through the labitbu leaf. This proves control of
+/// the key the labitbu was generated for.
+pub fn sign_message_labitbu_leaf(
+    signer: &dyn Signer,
+    payload_bytes: Vec<u8>,
+    message: &str,
+    format: Bip322Format,
+) -> Result<String, Error> {
+    let pubkey = signer.get_xonly_pubkey()?;
+    let spend_info = create_taproot_spend_info(pubkey, payload_bytes)?;
+    let challenge = ScriptBuf::new_p2tr_tweaked(spend_info.output_key());
+    let to_spend = to_spend(&challenge, message.as_bytes());
+
+    let mut psbt = Psbt::from_unsigned_tx(to_sign(&to_spend))?;
+    psbt.inputs[0].witness_utxo = Some(to_spend.output[0].clone());
+    add_labitbu_leaf(&mut psbt.inputs[0], pubkey, &spend_info);
+    crate::sign_labitbu_input(&mut psbt, 0, signer)?;
+    finalize_labitbu_input(&mut psbt, 0)?;
+
+    Ok(encode(&psbt.extract_tx_unchecked_fee_rate(), format))
+}
+
+/// Verifies a simple or full BIP322 `signature` of `message` by `address`.
+///
+/// Taproot addresses are supported, spent either by key path or through a
+/// single-key leaf such as the labitbu leaf, as are P2WPKH addresses.
+pub fn verify_message(address: &Address, message: &str, signature: &str) -> Result<(), Error> {
+    let challenge = address.script_pubkey();
+    if !challenge.is_p2tr() && !challenge.is_p2wpkh() {
+        return Err(Error::InvalidProof(
+            "only taproot and p2wpkh addresses are supported",
+        ));
+    }
+    let bytes = STANDARD
+        .decode(signature.trim())
+        .map_err(|_| Error::InvalidProof("signature is not base64"))?;
+    let to_spend = to_spend(&challenge, message.as_bytes());
+
+    let to_sign = match deserialize::<Witness>(&bytes) {
+        Ok(witness) => {
+            let mut to_sign = to_sign(&to_spend);
+            to_sign.input[0].witness = witness;
+            to_sign
+        }
+        Err(_) => {
+            let full: Transaction =
+                deserialize(&bytes).map_err(|_| Error::InvalidProof("malformed signature"))?;
+            // The signer may pick the version, locktime and sequence, to
+            // prove timelocked scripts; the signature commits to them.
+            let expected = to_sign(&to_spend);
+            if full.output != expected.output {
+                return Err(Error::InvalidProof(
+                    "to_sign must have a single OP_RETURN output",
+                ));
+            }
+            if full.input.len() != 1 {
+                return Err(Error::InvalidProof("proof of funds is not supported"));
+            }
+            if full.input[0].previous_output != expected.input[0].previous_output {
+                return Err(Error::InvalidProof("does not spend the message challenge"));
+            }
+            full
+        }
+    };
+
+    verify_taproot_spends(&to_sign, &to_spend.output)
+}
+
+/// A labitbu as listed in `labitbu.json`: its number and mint txid.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LabitbuRecord {
+    pub id: u32,
+    pub txid: Txid,
+}
+
+/// The message an ownership proof signs. `statement` is whatever the
+/// verifier asked to be signed, usually including a nonce so proofs cannot
+/// be replayed.
+pub fn ownership_message(labitbu: &LabitbuRecord, statement: &str) -> String {
+    format!(
+        "I own labitbu #{} minted in {}.\n{}",
+        labitbu.id, labitbu.txid, statement
+    )
+}
+
+/// A BIP322 proof that whoever controls `address` claims `labitbu`.
+///
+/// The proof only shows control of the address. Whether the labitbu's sat
+/// currently sits there, or whether the address is the labitbu's deposit
+/// address, is for the verifier to check against the chain.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnershipProof {
+    pub labitbu: LabitbuRecord,
+    pub address: String,
+    pub statement: String,
+    pub signature: String,
+}
+
+/// Proves ownership of `labitbu` with `signer`: through the labitbu leaf of
+/// its deposit if `payload_bytes` is given, otherwise with the BIP86 holder
+/// address of the key. Labitbus held anywhere else are proven with
+/// [`ownership_proof_from_signature`].
+pub fn prove_ownership(
+    labitbu: LabitbuRecord,
+    statement: &str,
+    signer: &dyn Signer,
+    payload_bytes: Option<Vec<u8>>,
+    format: Bip322Format,
+) -> Result<OwnershipProof, Error> {
+    let message = ownership_message(&labitbu, statement);
+    let pubkey = signer.get_xonly_pubkey()?;
+    let (address, signature) = match payload_bytes {
+        Some(payload_bytes) => {
+            let spend_info = create_taproot_spend_info(pubkey, payload_bytes.clone())?;
+            (
+                Address::p2tr_tweaked(spend_info.output_key(), Network::Bitcoin),
+                sign_message_labitbu_leaf(signer, payload_bytes, &message, format)?,
+            )
+        }
+        None => (
+            Address::p2tr(
+                &Secp256k1::verification_only(),
+                pubkey,
+                None,
+                Network::Bitcoin,
+            ),
+            sign_message_key_path(signer, &message, format)?,
+        ),
+    };
+
+    Ok(OwnershipProof {
+        labitbu,
+        address: address.to_string(),
+        statement: statement.to_string(),
+        signature,
+    })
+}
+
+/// Wraps a BIP322 `signature` of [`ownership_message`] that the wallet
+/// holding `address` produced itself, checking it before it is handed out.
+pub fn ownership_proof_from_signature(
+    labitbu: LabitbuRecord,
+    statement: &str,
+    address: &Address,
+    signature: &str,
+) -> Result<OwnershipProof, Error> {
+    verify_message(address, &ownership_message(&labitbu, statement), signature)?;
+
+    Ok(OwnershipProof {
+        labitbu,
+        address: address.to_string(),
+        statement: statement.to_string(),
+        signature: signature.trim().to_string(),
+    })
+}
+
+/// Verifies `proof` and that it names a labitbu from `known`, the entries
+/// of `labitbu.json`.
+pub fn verify_ownership(proof: &OwnershipProof, known: &[LabitbuRecord]) -> Result<(), Error> {
+    if !known.contains(&proof.labitbu) {
+        return Err(Error::InvalidProof("not a known labitbu"));
+    }
+    let address = Address::from_str(&proof.address)?.require_network(Network::Bitcoin)?;
+
+    verify_message(
+        &address,
+        &ownership_message(&proof.labitbu, &proof.statement),
+        &proof.signature,
+    )
+}
+
+#[wasm_bindgen]
+pub fn sign_bip322(
+    key: &EphemeralKey,
+    message: &str,
+    payload_bytes: Option<Vec<u8>>,
+    full: bool,
+) -> Result<String, JsValue> {
+    let format = if full {
+        Bip322Format::Full
+    } else {
+        Bip322Format::Simple
+    };
+
+    match payload_bytes {
+        Some(payload_bytes) => {
+            sign_message_labitbu_leaf(key.keypair(), payload_bytes, message, format)
+        }
+        None => sign_message_key_path(key.keypair(), message, format),
+    }
+    .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen]
+pub fn verify_bip322(address: &str, message: &str, signature: &str) -> Result<(), JsValue> {
+    let address = Address::from_str(address)
+        .and_then(|a| a.require_network(Network::Bitcoin))
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    verify_message(&address, message, signature).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(unchecked_return_type = "OwnershipProof")]
+pub fn prove_labitbu_ownership(
+    key: &EphemeralKey,
+    labitbu: JsValue,
+    statement: &str,
+    payload_bytes: Option<Vec<u8>>,
+    full: bool,
+) -> Result<JsValue, JsValue> {
+    let labitbu: LabitbuRecord = serde_wasm_bindgen::from_value(labitbu)
+        .map_err(|e| JsValue::from_str(&format!("labitbu: {}", e)))?;
+    let format = if full {
+        Bip322Format::Full
+    } else {
+        Bip322Format::Simple
+    };
+
+    let proof = prove_ownership(labitbu, statement, key.keypair(), payload_bytes, format)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    serde_wasm_bindgen::to_value(&proof).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen]
+pub fn labitbu_ownership_message(labitbu: JsValue, statement: &str) -> Result<String, JsValue> {
+    let labitbu: LabitbuRecord = serde_wasm_bindgen::from_value(labitbu)
+        .map_err(|e| JsValue::from_str(&format!("labitbu: {}", e)))?;
+
+    Ok(ownership_message(&labitbu, statement))
+}
+
+#[wasm_bindgen(unchecked_return_type = "OwnershipProof")]
+pub fn labitbu_ownership_from_signature(
+    labitbu: JsValue,
+    statement: &str,
+    address: &str,
+    signature: &str,
+) -> Result<JsValue, JsValue> {
+    let labitbu: LabitbuRecord = serde_wasm_bindgen::from_value(labitbu)
+        .map_err(|e| JsValue::from_str(&format!("labitbu: {}", e)))?;
+    let address = Address::from_str(address)
+        .and_then(|a| a.require_network(Network::Bitcoin))
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let proof = ownership_proof_from_signature(labitbu, statement, &address, signature)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    serde_wasm_bindgen::to_value(&proof).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen]
+pub fn verify_labitbu_ownership(proof: JsValue, labitbus: JsValue) -> Result<(), JsValue> {
+    let proof: OwnershipProof = serde_wasm_bindgen::from_value(proof)
+        .map_err(|e| JsValue::from_str(&format!("proof: {}", e)))?;
+    let labitbus: Vec<LabitbuRecord> = serde_wasm_bindgen::from_value(labitbus)
+        .map_err(|e| JsValue::from_str(&format!("labitbus: {}", e)))?;
+
+    verify_ownership(&proof, &labitbus).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::Keypair;
+
+    #[test]
+    fn matches_bip322_vectors() {
+        assert_eq!(
+            message_hash(b"").to_string(),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            message_hash(b"Hello World").to_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+
+        let address =
+            Address::from_str("bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3")
+                .unwrap()
+                .assume_checked();
+        let signature = "AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ==";
+        verify_message(&address, "Hello World", signature).unwrap();
+        assert!(verify_message(&address, "Hello World!", signature).is_err());
+
+        let address = Address::from_str("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l")
+            .unwrap()
+            .assume_checked();
+        let signature = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+        verify_message(&address, "Hello World", signature).unwrap();
+        assert!(verify_message(&address, "Hello World!", signature).is_err());
+    }
+
+    #[test]
+    fn full_proofs_must_have_the_to_sign_shape() {
+        let keypair = Keypair::new(&Secp256k1::new(), &mut rand::thread_rng());
+        let address = Address::p2tr(
+            &Secp256k1::new(),
+            keypair.x_only_public_key().0,
+            None,
+            Network::Bitcoin,
+        );
+        let signature = sign_message_key_path(&keypair, "hi", Bip322Format::Full).unwrap();
+        verify_message(&address, "hi", &signature).unwrap();
+
+        // A wallet proving a timelocked script signs its own version,
+        // locktime and sequence.
+        let to_spend = to_spend(&address.script_pubkey(), b"hi");
+        let mut timelocked = to_sign(&to_spend);
+        timelocked.version = transaction::Version(2);
+        timelocked.lock_time = absolute::LockTime::from_height(840_000).unwrap();
+        timelocked.input[0].sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+        let mut psbt = Psbt::from_unsigned_tx(timelocked).unwrap();
+        psbt.inputs[0].witness_utxo = Some(to_spend.output[0].clone());
+        psbt.inputs[0].tap_internal_key = Some(keypair.x_only_public_key().0);
+        sign_psbt_with(&mut psbt, &keypair).unwrap();
+        let mut timelocked = psbt.unsigned_tx.clone();
+        timelocked.input[0].witness =
+            Witness::from_slice(&[psbt.inputs[0].tap_key_sig.unwrap().to_vec()]);
+        let reencode = |to_sign: &Transaction| STANDARD.encode(serialize(to_sign));
+        verify_message(&address, "hi", &reencode(&timelocked)).unwrap();
+
+        let mut tampered = timelocked.clone();
+        tampered.version = transaction::Version(3);
+        assert!(verify_message(&address, "hi", &reencode(&tampered)).is_err());
+        let mut paying = timelocked;
+        paying.output[0].script_pubkey = address.script_pubkey();
+        assert!(matches!(
+            verify_message(&address, "hi", &reencode(&paying)),
+            Err(Error::InvalidProof(
+                "to_sign must have a single OP_RETURN output"
+            ))
+        ));
+    }
+
+    #[test]
+    fn ownership_proofs_with_either_key_or_a_wallet_signature() {
+        let keypair = Keypair::new(&Secp256k1::new(), &mut rand::thread_rng());
+        let labitbu = LabitbuRecord {
+            id: 1,
+            txid: "5a15dabc8f0c1656ccd07bd2739f683b4c562fb66487329a41f959c38f0cf7d3"
+                .parse()
+                .unwrap(),
+        };
+        let known = vec![labitbu.clone()];
+
+        for payload in [None, Some(vec![3u8; 4096])] {
+            for format in [Bip322Format::Simple, Bip322Format::Full] {
+                let proof = prove_ownership(
+                    labitbu.clone(),
+                    "nonce 42",
+                    &keypair,
+                    payload.clone(),
+                    format,
+                )
+                .unwrap();
+                verify_ownership(&proof, &known).unwrap();
+
+                let mut replayed = proof.clone();
+                replayed.statement = "nonce 43".to_string();
+                assert!(verify_ownership(&replayed, &known).is_err());
+                assert!(matches!(
+                    verify_ownership(&proof, &[]),
+                    Err(Error::InvalidProof("not a known labitbu"))
+                ));
+            }
+        }
+
+        // A wallet signing the ownership message for its own address.
+        let holder = Address::p2tr(
+            &Secp256k1::new(),
+            keypair.x_only_public_key().0,
+            None,
+            Network::Bitcoin,
+        );
+        let message = ownership_message(&labitbu, "nonce 44");
+        let signature = sign_message_key_path(&keypair, &message, Bip322Format::Simple).unwrap();
+        let proof =
+            ownership_proof_from_signature(labitbu.clone(), "nonce 44", &holder, &signature)
+                .unwrap();
+        verify_ownership(&proof, &known).unwrap();
+        assert!(ownership_proof_from_signature(labitbu, "nonce 45", &holder, &signature).is_err());
+    }
+}
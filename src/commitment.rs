@@ -0,0 +1,103 @@
+use bitcoin::{
+    hashes::Hash,
+    taproot::{LeafVersion, TapLeafHash},
+    Address, Network, ScriptBuf, XOnlyPublicKey,
+};
+use serde::Serialize;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{create_taproot_spend_info, parse_labitbu_key, spend_script, Error};
+
+/// Everything a labitbu deposit output commits to, hex encoded for indexers
+/// and explorers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TaprootCommitment {
+    pub address: String,
+    pub script_pubkey: String,
+    pub output_key: String,
+    /// Parity of the full output key: 0 for even, 1 for odd.
+    pub parity: u8,
+    pub internal_key: String,
+    pub tweak: String,
+    pub merkle_root: String,
+    pub leaf_hash: String,
+    pub control_block: String,
+}
+
+/// Computes the taproot commitment of the deposit output for `pubkey` and
+/// `payload_bytes`, the same one [`create_deposit_address`] encodes.
+///
+/// [`create_deposit_address`]: crate::create_deposit_address
+pub fn taproot_commitment(
+    pubkey: XOnlyPublicKey,
+    payload_bytes: Vec<u8>,
+) -> Result<TaprootCommitment, Error> {
+    let spend_info = create_taproot_spend_info(pubkey, payload_bytes)?;
+    let script = spend_script(pubkey);
+    let control_block = spend_info
+        .control_block(&(script.clone(), LeafVersion::TapScript))
+        .expect("labitbu leaf is in its own tree");
+    let merkle_root = spend_info
+        .merkle_root()
+        .expect("labitbu tree has a script leaf");
+
+    Ok(TaprootCommitment {
+        address: Address::p2tr_tweaked(spend_info.output_key(), Network::Bitcoin).to_string(),
+        script_pubkey: hex::encode(ScriptBuf::new_p2tr_tweaked(spend_info.output_key())),
+        output_key: spend_info.output_key().to_string(),
+        parity: spend_info.output_key_parity().to_u8(),
+        internal_key: spend_info.internal_key().to_string(),
+        tweak: hex::encode(spend_info.tap_tweak().to_byte_array()),
+        merkle_root: hex::encode(merkle_root.to_byte_array()),
+        leaf_hash: hex::encode(
+            TapLeafHash::from_script(&script, LeafVersion::TapScript).to_byte_array(),
+        ),
+        control_block: hex::encode(control_block.serialize()),
+    })
+}
+
+#[wasm_bindgen(unchecked_return_type = "TaprootCommitment")]
+pub fn deposit_commitment(pubkey_hex: &str, payload_bytes: Vec<u8>) -> Result<JsValue, JsValue> {
+    let pubkey = parse_labitbu_key(pubkey_hex)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?
+        .pubkey;
+
+    let commitment =
+        taproot_commitment(pubkey, payload_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    serde_wasm_bindgen::to_value(&commitment).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::taproot::ControlBlock;
+    use secp256k1::Secp256k1;
+
+    use crate::nums_from_tag;
+
+    #[test]
+    fn commitment_fields_agree_with_each_other() {
+        let pubkey = nums_from_tag(b"depositor");
+        let commitment = taproot_commitment(pubkey, vec![5u8; 4096]).unwrap();
+
+        let control_block =
+            ControlBlock::decode(&hex::decode(&commitment.control_block).unwrap()).unwrap();
+        let output_key: XOnlyPublicKey = commitment.output_key.parse().unwrap();
+        assert!(control_block.verify_taproot_commitment(
+            &Secp256k1::verification_only(),
+            output_key,
+            &spend_script(pubkey),
+        ));
+        assert_eq!(control_block.output_key_parity.to_u8(), commitment.parity);
+        assert_eq!(control_block.merkle_branch.len(), 128);
+        assert_eq!(
+            commitment.internal_key,
+            nums_from_tag(b"Labitbu").to_string()
+        );
+        assert_eq!(
+            commitment.script_pubkey,
+            format!("5120{}", commitment.output_key)
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use bitcoin::{
+    hashes::{sha256, Hash},
+    taproot::LeafVersion,
+    XOnlyPublicKey,
+};
+use serde::Serialize;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{create_taproot_spend_info, parse_labitbu_key, spend_script, Error};
+
+const INPUT_CHARSET: &str =
+    "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn poly_mod(mut c: u64, val: u64) -> u64 {
+    let c0 = c >> 35;
+    c = ((c & 0x7ffffffff) << 5) ^ val;
+    if c0 & 1 != 0 {
+        c ^= 0xf5dee51989;
+    }
+    if c0 & 2 != 0 {
+        c ^= 0xa9fdca3312;
+    }
+    if c0 & 4 != 0 {
+        c ^= 0x1bab10e32d;
+    }
+    if c0 & 8 != 0 {
+        c ^= 0x3706b1677a;
+    }
+    if c0 & 16 != 0 {
+        c ^= 0x644d626ffd;
+    }
+    c
+}
+
+/// The BIP380 checksum of `descriptor`, or `None` if it uses a character
+/// descriptors cannot contain.
+pub fn descriptor_checksum(descriptor: &str) -> Option<String> {
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let pos = INPUT_CHARSET.find(ch)? as u64;
+        c = poly_mod(c, pos & 31);
+        class = class * 3 + (pos >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = poly_mod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = poly_mod(c, class);
+    }
+    for _ in 0..8 {
+        c = poly_mod(c, 0);
+    }
+    c ^= 1;
+
+    Some(
+        (0..8)
+            .map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char)
+            .collect(),
+    )
+}
+
+/// A watch-only `rawtr(<output key>)#<checksum>` descriptor for the deposit
+/// output of `pubkey` and `payload_bytes`.
+///
+/// The internal key is the unspendable NUMS point, so the output key alone
+/// describes everything a wallet needs to watch the deposit.
+pub fn deposit_descriptor(pubkey: XOnlyPublicKey, payload_bytes: Vec<u8>) -> Result<String, Error> {
+    let spend_info = create_taproot_spend_info(pubkey, payload_bytes)?;
+    let descriptor = format!("rawtr({})", spend_info.output_key());
+    let checksum = descriptor_checksum(&descriptor).expect("hex is in the descriptor charset");
+
+    Ok(format!("{}#{}", descriptor, checksum))
+}
+
+/// What it takes to spend a deposit without this crate: the descriptor to
+/// watch, and the pieces of the labitbu leaf's witness.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SpendInfoBundle {
+    pub descriptor: String,
+    pub internal_key: String,
+    pub leaf_script: String,
+    pub leaf_version: u8,
+    pub control_block: String,
+    /// SHA256 of the payload, so the payload itself can be checked against
+    /// the bundle.
+    pub payload_sha256: String,
+}
+
+/// Bundles the spend information for the deposit of `pubkey` and
+/// `payload_bytes`.
+pub fn spend_info_bundle(
+    pubkey: XOnlyPublicKey,
+    payload_bytes: Vec<u8>,
+) -> Result<SpendInfoBundle, Error> {
+    let payload_sha256 = sha256::Hash::hash(&payload_bytes);
+    let descriptor = deposit_descriptor(pubkey, payload_bytes.clone())?;
+    let spend_info = create_taproot_spend_info(pubkey, payload_bytes)?;
+    let script = spend_script(pubkey);
+    let control_block = spend_info
+        .control_block(&(script.clone(), LeafVersion::TapScript))
+        .expect("labitbu leaf is in its own tree");
+
+    Ok(SpendInfoBundle {
+        descriptor,
+        internal_key: spend_info.internal_key().to_string(),
+        leaf_script: script.to_hex_string(),
+        leaf_version: LeafVersion::TapScript.to_consensus(),
+        control_block: hex::encode(control_block.serialize()),
+        payload_sha256: hex::encode(payload_sha256.to_byte_array()),
+    })
+}
+
+#[wasm_bindgen]
+pub fn deposit_watch_descriptor(
+    pubkey_hex: &str,
+    payload_bytes: Vec<u8>,
+) -> Result<String, JsValue> {
+    let pubkey = parse_labitbu_key(pubkey_hex)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?
+        .pubkey;
+
+    deposit_descriptor(pubkey, payload_bytes).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(unchecked_return_type = "SpendInfoBundle")]
+pub fn deposit_spend_info(pubkey_hex: &str, payload_bytes: Vec<u8>) -> Result<JsValue, JsValue> {
+    let pubkey = parse_labitbu_key(pubkey_hex)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?
+        .pubkey;
+
+    let bundle =
+        spend_info_bundle(pubkey, payload_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    serde_wasm_bindgen::to_value(&bundle).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{nums_from_tag, taproot_commitment};
+
+    #[test]
+    fn checksum_matches_bip380_vectors() {
+        assert_eq!(
+            descriptor_checksum("raw(deadbeef)").as_deref(),
+            Some("89f8spxm")
+        );
+        assert_eq!(
+            descriptor_checksum("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)").as_deref(),
+            Some("02wpgw69")
+        );
+        assert_eq!(descriptor_checksum("raw(\u{e9})"), None);
+    }
+
+    #[test]
+    fn bundle_describes_the_deposit_output() {
+        let pubkey = nums_from_tag(b"depositor");
+        let payload = vec![4u8; 4096];
+        let commitment = taproot_commitment(pubkey, payload.clone()).unwrap();
+
+        let bundle = spend_info_bundle(pubkey, payload).unwrap();
+
+        assert!(bundle
+            .descriptor
+            .starts_with(&format!("rawtr({})#", commitment.output_key)));
+        assert_eq!(bundle.control_block, commitment.control_block);
+        assert_eq!(bundle.internal_key, commitment.internal_key);
+        assert_eq!(bundle.leaf_version, 0xc0);
+    }
+}
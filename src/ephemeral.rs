@@ -0,0 +1,312 @@
+use std::str::FromStr;
+
+use bip39::Mnemonic;
+use bitcoin::{Psbt, XOnlyPublicKey};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::RngCore;
+use secp256k1::{Keypair, Secp256k1, SecretKey};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    deserialize_psbt, finalize_labitbu_inputs, mnemonic_keypair, sign_labitbu_input, spend_script,
+    Error, PsbtResult,
+};
+
+const BACKUP_MAGIC: &[u8; 4] = b"LBKY";
+const BACKUP_VERSION: u8 = 1;
+/// magic, version, log2 of the scrypt cost, scrypt r and p, salt, nonce.
+const HEADER_LEN: usize = 4 + 1 + 1 + 4 + 4 + 16 + 12;
+const CIPHERTEXT_LEN: usize = 32 + 16;
+
+/// scrypt cost used for new backups: N = 2^15, r = 8, p = 1.
+pub const DEFAULT_SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+/// Refuse to write or open backups cheaper than 2^14 rounds, which a
+/// password guesser would get through far too quickly. Tests lower it so
+/// they do not spend seconds in scrypt.
+#[cfg(not(test))]
+const MIN_SCRYPT_LOG_N: u8 = 14;
+#[cfg(test)]
+const MIN_SCRYPT_LOG_N: u8 = 4;
+/// scrypt needs `128 * r * N` bytes, so with r = 8 this caps a backup at
+/// 128 MiB and a tampered file cannot exhaust a wasm memory.
+const MAX_SCRYPT_LOG_N: u8 = 17;
+
+fn check_scrypt_cost(log_n: u8) -> Result<(), Error> {
+    if log_n < MIN_SCRYPT_LOG_N {
+        return Err(Error::Backup("scrypt cost too low"));
+    }
+    if log_n > MAX_SCRYPT_LOG_N {
+        return Err(Error::Backup("scrypt cost too high"));
+    }
+    Ok(())
+}
+
+fn backup_key(password: &str, salt: &[u8], log_n: u8, r: u32, p: u32) -> Result<Key, Error> {
+    let params =
+        scrypt::Params::new(log_n, r, p, 32).map_err(|_| Error::Backup("bad scrypt parameters"))?;
+    let mut key = Key::default();
+    scrypt::scrypt(password.as_bytes(), salt, &params, &mut key)
+        .expect("32 bytes is a valid scrypt output length");
+    Ok(key)
+}
+
+/// Encrypts the secret of `keypair` under `password`.
+///
+/// The key is stretched with scrypt at cost `2^log_n` and the secret sealed
+/// with ChaCha20-Poly1305. The header, which carries the scrypt parameters,
+/// salt and nonce, is authenticated along with it.
+pub fn encrypt_backup(keypair: &Keypair, password: &str, log_n: u8) -> Result<Vec<u8>, Error> {
+    check_scrypt_cost(log_n)?;
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let mut backup = Vec::with_capacity(HEADER_LEN + CIPHERTEXT_LEN);
+    backup.extend_from_slice(BACKUP_MAGIC);
+    backup.push(BACKUP_VERSION);
+    backup.push(log_n);
+    backup.extend_from_slice(&SCRYPT_R.to_be_bytes());
+    backup.extend_from_slice(&SCRYPT_P.to_be_bytes());
+    backup.extend_from_slice(&salt);
+    backup.extend_from_slice(&nonce);
+
+    let key = backup_key(password, &salt, log_n, SCRYPT_R, SCRYPT_P)?;
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &keypair.secret_bytes(),
+                aad: &backup,
+            },
+        )
+        .expect("encrypting a 32-byte secret cannot fail");
+    backup.extend_from_slice(&ciphertext);
+
+    Ok(backup)
+}
+
+/// Opens a backup made by [`encrypt_backup`].
+pub fn decrypt_backup(backup: &[u8], password: &str) -> Result<Keypair, Error> {
+    if backup.len() != HEADER_LEN + CIPHERTEXT_LEN || &backup[..4] != BACKUP_MAGIC {
+        return Err(Error::Backup("not a labitbu key backup"));
+    }
+    if backup[4] != BACKUP_VERSION {
+        return Err(Error::Backup("unsupported backup version"));
+    }
+    let (header, ciphertext) = backup.split_at(HEADER_LEN);
+    let log_n = header[5];
+    check_scrypt_cost(log_n)?;
+    let r = u32::from_be_bytes(header[6..10].try_into().unwrap());
+    let p = u32::from_be_bytes(header[10..14].try_into().unwrap());
+    if r != SCRYPT_R || p != SCRYPT_P {
+        return Err(Error::Backup("unsupported scrypt parameters"));
+    }
+    let salt = &header[14..30];
+    let nonce = &header[30..42];
+
+    let key = backup_key(password, salt, log_n, SCRYPT_R, SCRYPT_P)?;
+    let secret = ChaCha20Poly1305::new(&key)
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| Error::Backup("wrong password or corrupted backup"))?;
+    let secret_key =
+        SecretKey::from_slice(&secret).map_err(|_| Error::Backup("invalid secret key"))?;
+
+    Ok(Keypair::from_secret_key(&Secp256k1::new(), &secret_key))
+}
+
+/// Signs and finalizes every labitbu input of `psbt` locked to `keypair`.
+///
+/// Inputs for other keys, such as funding inputs, are left for their own
+/// wallets.
+pub fn sign_and_finalize_labitbu_inputs(psbt: &mut Psbt, keypair: &Keypair) -> Result<(), Error> {
+    let script = spend_script(keypair.x_only_public_key().0);
+    let ours: Vec<usize> = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, psbt_in)| psbt_in.tap_scripts.values().any(|(s, _)| *s == script))
+        .map(|(index, _)| index)
+        .collect();
+    if ours.is_empty() {
+        return Err(Error::NoDeposits);
+    }
+
+    for index in ours {
+        sign_labitbu_input(psbt, index, keypair)?;
+    }
+    finalize_labitbu_inputs(psbt)
+}
+
+/// A keypair generated in the browser for users whose wallet cannot sign
+/// taproot script paths.
+///
+/// Its public key stands in wherever the other bindings take a pubkey. Back
+/// it up with [`EphemeralKey::backup`] before funding the deposit: the
+/// deposit address has no key path, so without the secret the funds are
+/// stuck.
+#[wasm_bindgen]
+pub struct EphemeralKey {
+    keypair: Keypair,
+}
+
+impl EphemeralKey {
+    pub fn keypair(&self) -> &Keypair {
+        &self.keypair
+    }
+}
+
+impl From<Keypair> for EphemeralKey {
+    fn from(keypair: Keypair) -> Self {
+        EphemeralKey { keypair }
+    }
+}
+
+#[wasm_bindgen]
+impl EphemeralKey {
+    /// Generates a fresh keypair from the platform's secure randomness.
+    #[wasm_bindgen(constructor)]
+    pub fn generate() -> EphemeralKey {
+        Keypair::new(&Secp256k1::new(), &mut rand::thread_rng()).into()
+    }
+
+    pub fn from_backup(backup: Vec<u8>, password: &str) -> Result<EphemeralKey, JsValue> {
+        decrypt_backup(&backup, password)
+            .map(Into::into)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Restores the labitbu key at `index` under a BIP39 mnemonic; see
+    /// [`LABITBU_KEY_PATH`](crate::LABITBU_KEY_PATH).
+    pub fn from_mnemonic(
+        words: &str,
+        passphrase: &str,
+        index: u32,
+    ) -> Result<EphemeralKey, JsValue> {
+        let mnemonic =
+            Mnemonic::from_str(words.trim()).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        mnemonic_keypair(&mnemonic, passphrase, index)
+            .map(|(keypair, _)| keypair.into())
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// The x-only public key in hex.
+    #[wasm_bindgen(getter)]
+    pub fn pubkey(&self) -> String {
+        let pubkey: XOnlyPublicKey = self.keypair.x_only_public_key().0;
+        pubkey.to_string()
+    }
+
+    /// The secret, encrypted under `password`.
+    pub fn backup(&self, password: &str) -> Result<Vec<u8>, JsValue> {
+        encrypt_backup(&self.keypair, password, DEFAULT_SCRYPT_LOG_N)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// Signs and finalizes the labitbu inputs of a mint PSBT that spend this
+    /// key's deposits.
+    pub fn sign_mint(&self, psbt_bytes: Vec<u8>) -> Result<PsbtResult, JsValue> {
+        let mut psbt =
+            deserialize_psbt(&psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        sign_and_finalize_labitbu_inputs(&mut psbt, &self.keypair)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        Ok(psbt.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        absolute, hashes::Hash, transaction, Amount, OutPoint, ScriptBuf, Transaction, TxIn, TxOut,
+        Txid,
+    };
+
+    use crate::{add_labitbu_leaf, create_taproot_spend_info, verify_finalized};
+
+    #[test]
+    fn backup_round_trips_and_rejects_wrong_password() {
+        let keypair = Keypair::new(&Secp256k1::new(), &mut rand::thread_rng());
+        let backup = encrypt_backup(&keypair, "correct horse", MIN_SCRYPT_LOG_N).unwrap();
+
+        assert_eq!(decrypt_backup(&backup, "correct horse").unwrap(), keypair);
+        assert!(matches!(
+            decrypt_backup(&backup, "battery staple"),
+            Err(Error::Backup("wrong password or corrupted backup"))
+        ));
+
+        let mut tampered = backup.clone();
+        tampered[5] = 5;
+        assert!(decrypt_backup(&tampered, "correct horse").is_err());
+
+        let mut greedy = backup.clone();
+        greedy[6..10].copy_from_slice(&(1u32 << 20).to_be_bytes());
+        assert!(matches!(
+            decrypt_backup(&greedy, "correct horse"),
+            Err(Error::Backup("unsupported scrypt parameters"))
+        ));
+        assert!(matches!(
+            encrypt_backup(&keypair, "correct horse", MAX_SCRYPT_LOG_N + 1),
+            Err(Error::Backup("scrypt cost too high"))
+        ));
+        assert!(matches!(
+            encrypt_backup(&keypair, "correct horse", MIN_SCRYPT_LOG_N - 1),
+            Err(Error::Backup("scrypt cost too low"))
+        ));
+        let mut cheap = backup.clone();
+        cheap[5] = MIN_SCRYPT_LOG_N - 1;
+        assert!(matches!(
+            decrypt_backup(&cheap, "correct horse"),
+            Err(Error::Backup("scrypt cost too low"))
+        ));
+    }
+
+    #[test]
+    fn signs_only_its_own_deposits() {
+        let key = EphemeralKey::generate();
+        let pubkey = key.keypair().x_only_public_key().0;
+        let spend_info = create_taproot_spend_info(pubkey, vec![7u8; 4096]).unwrap();
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+        };
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version(2),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(prevout);
+        add_labitbu_leaf(&mut psbt.inputs[0], pubkey, &spend_info);
+
+        let other = EphemeralKey::generate();
+        assert!(matches!(
+            sign_and_finalize_labitbu_inputs(&mut psbt.clone(), other.keypair()),
+            Err(Error::NoDeposits)
+        ));
+
+        sign_and_finalize_labitbu_inputs(&mut psbt, key.keypair()).unwrap();
+        verify_finalized(&psbt).unwrap();
+    }
+}
@@ -0,0 +1,200 @@
+use std::fmt;
+
+use bitcoin::{address, bip32, psbt, sighash, taproot::TaprootBuilderError, Amount, OutPoint};
+
+/// Errors returned by the transaction builders in this crate.
+#[derive(Debug)]
+pub enum Error {
+    /// No labitbu deposits were supplied.
+    NoDeposits,
+    /// No funding inputs were supplied to pay the fee.
+    NoFunding,
+    /// The inputs do not cover the outputs plus fee.
+    InsufficientFunds { available: Amount, required: Amount },
+    /// A funding input's prevout does not pay to the key it was described with.
+    FundingScriptMismatch(OutPoint),
+    /// The PSBT has no way to tell how an input will be signed.
+    UnsupportedInput(OutPoint),
+    /// The requested input index does not exist.
+    NoSuchInput(usize),
+    /// The input has no labitbu leaf to sign or finalize.
+    NotLabitbuInput(usize),
+    /// The input has no `witness_utxo`.
+    MissingUtxo(usize),
+    /// The input has not been signed.
+    MissingSignature(usize),
+    /// The input's signature does not use the sighash type it asked for.
+    SighashMismatch(usize),
+    /// The PSBT asks for a sighash type taproot does not support.
+    InvalidSighashType,
+    /// The requested output index does not exist.
+    NoSuchOutput(usize),
+    /// A TRUC transaction is over its policy size limit.
+    TrucTooLarge { vsize: u64, max: u64 },
+    /// The parent has no pay-to-anchor output to spend.
+    NoAnchor,
+    /// A marketplace listing or purchase is malformed.
+    InvalidListing(&'static str),
+    /// The outputs spend more sats than the inputs provide.
+    SatFlowUnderflow,
+    /// A labitbu sat would not end up where the transaction means to send it.
+    SatNotPreserved { input: usize },
+    /// An output would be below the dust limit of its script.
+    DustOutput {
+        vout: usize,
+        value: Amount,
+        threshold: Amount,
+    },
+    /// A fee computation overflowed.
+    FeeOverflow,
+    /// An input's witness does not satisfy the output it spends.
+    InvalidWitness { input: usize, reason: &'static str },
+    /// A different number of spent outputs than inputs was given.
+    PrevoutCount { inputs: usize, prevouts: usize },
+    /// A labitbu key could not be parsed.
+    InvalidKey(&'static str),
+    /// A key could not be derived from an xpub.
+    Bip32(bip32::Error),
+    /// An external signer misbehaved.
+    Signer(&'static str),
+    /// A MuSig2 key aggregation or signing session failed.
+    Musig(&'static str),
+    /// The destination is a silent payment address, which labitbu
+    /// transactions cannot pay.
+    SilentPayment,
+    /// A BIP322 signature or ownership proof is malformed or unsupported.
+    InvalidProof(&'static str),
+    /// A BIP39 mnemonic could not be parsed.
+    Mnemonic(bip39::Error),
+    /// An encrypted key backup could not be opened.
+    Backup(&'static str),
+    /// A labitbu image could not be rendered.
+    Image(String),
+    /// An address could not be parsed or is for the wrong network.
+    Address(address::ParseError),
+    /// The taproot tree for a payload could not be built.
+    Taproot(TaprootBuilderError),
+    /// The PSBT could not be created or updated.
+    Psbt(psbt::Error),
+    /// A BIP370 version 2 PSBT is malformed or cannot take the change.
+    PsbtV2(&'static str),
+    /// A signature hash could not be computed.
+    Sighash(sighash::TaprootError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoDeposits => write!(f, "no deposits provided"),
+            Error::NoFunding => write!(f, "no funding inputs provided"),
+            Error::InsufficientFunds {
+                available,
+                required,
+            } => write!(
+                f,
+                "insufficient funds: have {} sat, need {} sat",
+                available.to_sat(),
+                required.to_sat()
+            ),
+            Error::FundingScriptMismatch(outpoint) => {
+                write!(f, "funding input {} does not match its key", outpoint)
+            }
+            Error::UnsupportedInput(outpoint) => {
+                write!(f, "cannot tell how input {} is spent", outpoint)
+            }
+            Error::NoSuchInput(index) => write!(f, "no input at index {}", index),
+            Error::NotLabitbuInput(index) => write!(f, "input {} has no labitbu leaf", index),
+            Error::MissingUtxo(index) => write!(f, "input {} has no witness utxo", index),
+            Error::MissingSignature(index) => write!(f, "input {} is not signed", index),
+            Error::SighashMismatch(index) => {
+                write!(f, "input {} is signed with the wrong sighash type", index)
+            }
+            Error::InvalidSighashType => write!(f, "invalid taproot sighash type"),
+            Error::NoSuchOutput(index) => write!(f, "no output at index {}", index),
+            Error::TrucTooLarge { vsize, max } => {
+                write!(f, "TRUC transaction is {} vB, limit is {} vB", vsize, max)
+            }
+            Error::NoAnchor => write!(f, "no pay-to-anchor output"),
+            Error::InvalidListing(reason) => write!(f, "invalid listing: {}", reason),
+            Error::SatFlowUnderflow => write!(f, "outputs exceed inputs"),
+            Error::SatNotPreserved { input } => {
+                write!(
+                    f,
+                    "labitbu sat of input {} would not reach its output",
+                    input
+                )
+            }
+            Error::DustOutput {
+                vout,
+                value,
+                threshold,
+            } => write!(
+                f,
+                "output {} of {} is below the dust limit of {}",
+                vout, value, threshold
+            ),
+            Error::FeeOverflow => write!(f, "fee overflow"),
+            Error::InvalidWitness { input, reason } => {
+                write!(f, "input {} is invalid: {}", input, reason)
+            }
+            Error::PrevoutCount { inputs, prevouts } => {
+                write!(f, "{} spent outputs given for {} inputs", prevouts, inputs)
+            }
+            Error::InvalidKey(reason) => write!(f, "invalid key: {}", reason),
+            Error::Bip32(e) => write!(f, "bip32: {}", e),
+            Error::Signer(reason) => write!(f, "signer: {}", reason),
+            Error::Musig(reason) => write!(f, "musig: {}", reason),
+            Error::SilentPayment => write!(
+                f,
+                "silent payment addresses are not supported: labitbu deposits have no spendable key to derive the output from"
+            ),
+            Error::InvalidProof(reason) => write!(f, "invalid proof: {}", reason),
+            Error::Mnemonic(e) => write!(f, "mnemonic: {}", e),
+            Error::Backup(reason) => write!(f, "key backup: {}", reason),
+            Error::Image(message) => write!(f, "{}", message),
+            Error::Address(e) => write!(f, "address: {}", e),
+            Error::Taproot(e) => write!(f, "taproot: {}", e),
+            Error::Psbt(e) => write!(f, "psbt: {}", e),
+            Error::PsbtV2(reason) => write!(f, "psbt v2: {}", reason),
+            Error::Sighash(e) => write!(f, "sighash: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<address::ParseError> for Error {
+    fn from(e: address::ParseError) -> Self {
+        Error::Address(e)
+    }
+}
+
+impl From<bip32::Error> for Error {
+    fn from(e: bip32::Error) -> Self {
+        Error::Bip32(e)
+    }
+}
+
+impl From<bip39::Error> for Error {
+    fn from(e: bip39::Error) -> Self {
+        Error::Mnemonic(e)
+    }
+}
+
+impl From<TaprootBuilderError> for Error {
+    fn from(e: TaprootBuilderError) -> Self {
+        Error::Taproot(e)
+    }
+}
+
+impl From<psbt::Error> for Error {
+    fn from(e: psbt::Error) -> Self {
+        Error::Psbt(e)
+    }
+}
+
+impl From<sighash::TaprootError> for Error {
+    fn from(e: sighash::TaprootError) -> Self {
+        Error::Sighash(e)
+    }
+}
@@ -0,0 +1,275 @@
+use bitcoin::{
+    absolute, hashes::Hash, key::TweakedPublicKey, transaction, Amount, FeeRate, OutPoint, Psbt,
+    PubkeyHash, ScriptBuf, ScriptHash, Transaction, TxIn, TxOut, Txid, WPubkeyHash, WScriptHash,
+};
+use secp256k1::PublicKey;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    add_funding_input, add_labitbu_leaf, create_taproot_spend_info, nums_from_tag, predicted_vsize,
+    Error, FundingInput, FundingKind,
+};
+
+/// Script type of a fee-paying input, for estimates made before the wallet's
+/// UTXOs are known.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputType {
+    P2wpkh,
+    P2trKeyPath,
+    P2shP2wpkh,
+}
+
+/// Script type of a mint output.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+}
+
+impl OutputType {
+    /// A script_pubkey of this type, the same size as any real one.
+    fn placeholder_script(self) -> ScriptBuf {
+        match self {
+            OutputType::P2pkh => ScriptBuf::new_p2pkh(&PubkeyHash::all_zeros()),
+            OutputType::P2sh => ScriptBuf::new_p2sh(&ScriptHash::all_zeros()),
+            OutputType::P2wpkh => ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+            OutputType::P2wsh => ScriptBuf::new_p2wsh(&WScriptHash::all_zeros()),
+            OutputType::P2tr => ScriptBuf::new_p2tr_tweaked(
+                TweakedPublicKey::dangerous_assume_tweaked(nums_from_tag(b"estimate")),
+            ),
+        }
+    }
+}
+
+/// The shape of a mint to estimate: what it spends and what it creates.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MintShape {
+    /// Length of each deposit's payload; 4096 for a full labitbu.
+    pub payload_len: usize,
+    pub deposits: usize,
+    /// Wallet inputs paying the fee. With none, the deposits pay it.
+    #[serde(default)]
+    pub funding: Vec<InputType>,
+    /// Outputs of the mint, the labitbu destination first.
+    pub outputs: Vec<OutputType>,
+    /// Value of the labitbu output; the destination's dust limit if unset.
+    #[serde(default)]
+    pub postage: Option<u64>,
+}
+
+/// What a mint of a given shape costs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct MintEstimate {
+    /// Amount to send to each deposit address.
+    pub deposit_amount: u64,
+    pub mint_vsize: u64,
+    pub mint_fee: u64,
+    pub postage: u64,
+    /// Everything the minter spends: the deposits plus any fee paid by
+    /// funding inputs.
+    pub total: u64,
+}
+
+/// Estimates the cost of minting `shape` at `fee_rate`.
+///
+/// The estimate builds the mint PSBT itself, with leaves from
+/// [`create_taproot_spend_info`] and placeholder keys, and sizes it with
+/// [`predicted_vsize`], so it matches what the builders produce.
+pub fn estimate_mint(shape: &MintShape, fee_rate: FeeRate) -> Result<MintEstimate, Error> {
+    if shape.deposits == 0 {
+        return Err(Error::NoDeposits);
+    }
+    let destination = shape.outputs.first().ok_or(Error::NoSuchOutput(0))?;
+    let postage = shape
+        .postage
+        .map(Amount::from_sat)
+        .unwrap_or_else(|| destination.placeholder_script().minimal_non_dust());
+
+    let pubkey = nums_from_tag(b"estimate");
+    let spend_info = create_taproot_spend_info(pubkey, vec![0; shape.payload_len])?;
+    let deposit_script = ScriptBuf::new_p2tr_tweaked(spend_info.output_key());
+
+    let mut psbt = Psbt::from_unsigned_tx(Transaction {
+        version: transaction::Version(2),
+        lock_time: absolute::LockTime::ZERO,
+        input: (0..shape.deposits)
+            .map(|vout| TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), vout as u32),
+                ..Default::default()
+            })
+            .collect(),
+        output: shape
+            .outputs
+            .iter()
+            .map(|output| TxOut {
+                value: Amount::ZERO,
+                script_pubkey: output.placeholder_script(),
+            })
+            .collect(),
+    })?;
+    for psbt_in in psbt.inputs.iter_mut() {
+        psbt_in.witness_utxo = Some(TxOut {
+            value: Amount::ZERO,
+            script_pubkey: deposit_script.clone(),
+        });
+        add_labitbu_leaf(psbt_in, pubkey, &spend_info);
+    }
+    let wallet_key = PublicKey::from_x_only_public_key(pubkey, secp256k1::Parity::Even);
+    for (i, input_type) in shape.funding.iter().enumerate() {
+        let kind = match input_type {
+            InputType::P2wpkh => FundingKind::P2wpkh { pubkey: wallet_key },
+            InputType::P2trKeyPath => FundingKind::P2trKeyPath {
+                internal_key: pubkey,
+            },
+            InputType::P2shP2wpkh => FundingKind::P2shP2wpkh { pubkey: wallet_key },
+        };
+        let mut funding = FundingInput {
+            outpoint: OutPoint::new(Txid::all_zeros(), (shape.deposits + i) as u32),
+            prevout: TxOut {
+                value: Amount::ZERO,
+                script_pubkey: ScriptBuf::new(),
+            },
+            kind,
+        };
+        funding.prevout.script_pubkey = funding.expected_script_pubkey();
+        add_funding_input(&mut psbt, funding)?;
+    }
+
+    let mint_vsize = predicted_vsize(&psbt)?;
+    let mint_fee = fee_rate.fee_vb(mint_vsize).ok_or(Error::FeeOverflow)?;
+
+    // Deposits carry the postage, and the fee too when nothing else pays it.
+    let deposits_cover = if shape.funding.is_empty() {
+        postage + mint_fee
+    } else {
+        postage
+    };
+    let deposit_amount = deposits_cover
+        .to_sat()
+        .div_ceil(shape.deposits as u64)
+        .max(deposit_script.minimal_non_dust().to_sat());
+    let deposited = deposit_amount * shape.deposits as u64;
+    let total = if shape.funding.is_empty() {
+        deposited
+    } else {
+        deposited + mint_fee.to_sat()
+    };
+
+    Ok(MintEstimate {
+        deposit_amount,
+        mint_vsize,
+        mint_fee: mint_fee.to_sat(),
+        postage: postage.to_sat(),
+        total,
+    })
+}
+
+#[wasm_bindgen(unchecked_return_type = "MintEstimate")]
+pub fn estimate_mint_cost(shape: JsValue, fee_rate_sat_vb: u64) -> Result<JsValue, JsValue> {
+    let shape: MintShape = serde_wasm_bindgen::from_value(shape)
+        .map_err(|e| JsValue::from_str(&format!("shape: {}", e)))?;
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb)
+        .ok_or_else(|| JsValue::from_str("fee rate overflow"))?;
+
+    let estimate =
+        estimate_mint(&shape, fee_rate).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    serde_wasm_bindgen::to_value(&estimate).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{Address, Network};
+    use secp256k1::Secp256k1;
+
+    use crate::{build_funded_mint, Deposit};
+
+    #[test]
+    fn estimate_matches_funded_mint() {
+        let secp = Secp256k1::new();
+        let pubkey = nums_from_tag(b"depositor");
+        let payload = vec![7u8; 4096];
+        let spend_info = create_taproot_spend_info(pubkey, payload.clone()).unwrap();
+        let wallet_key = PublicKey::from_secret_key(
+            &secp,
+            &secp256k1::SecretKey::from_slice(&[2u8; 32]).unwrap(),
+        );
+        let wallet_script =
+            ScriptBuf::new_p2wpkh(&bitcoin::CompressedPublicKey(wallet_key).wpubkey_hash());
+        let destination = Address::p2tr(&secp, pubkey, None, Network::Bitcoin).to_string();
+        let change = Address::from_script(&wallet_script, Network::Bitcoin)
+            .unwrap()
+            .to_string();
+
+        let psbt = build_funded_mint(
+            vec![Deposit {
+                pubkey,
+                payload,
+                outpoint: OutPoint::new(Txid::all_zeros(), 0),
+                prevout: TxOut {
+                    value: Amount::from_sat(330),
+                    script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+                },
+            }],
+            &destination,
+            vec![FundingInput {
+                outpoint: OutPoint::new(Txid::all_zeros(), 1),
+                prevout: TxOut {
+                    value: Amount::from_sat(50_000),
+                    script_pubkey: wallet_script,
+                },
+                kind: FundingKind::P2wpkh { pubkey: wallet_key },
+            }],
+            &change,
+            FeeRate::from_sat_per_vb_u32(3),
+        )
+        .unwrap();
+
+        let estimate = estimate_mint(
+            &MintShape {
+                payload_len: 4096,
+                deposits: 1,
+                funding: vec![InputType::P2wpkh],
+                outputs: vec![OutputType::P2tr, OutputType::P2wpkh],
+                postage: None,
+            },
+            FeeRate::from_sat_per_vb_u32(3),
+        )
+        .unwrap();
+
+        assert_eq!(estimate.mint_vsize, predicted_vsize(&psbt).unwrap());
+        assert_eq!(estimate.mint_fee, 3 * estimate.mint_vsize);
+        assert_eq!(psbt.fee().unwrap().to_sat(), estimate.mint_fee);
+        assert_eq!(estimate.postage, 330);
+        assert_eq!(estimate.deposit_amount, 330);
+        assert_eq!(estimate.total, 330 + estimate.mint_fee);
+    }
+
+    #[test]
+    fn unfunded_deposits_carry_the_fee() {
+        let estimate = estimate_mint(
+            &MintShape {
+                payload_len: 4096,
+                deposits: 2,
+                funding: vec![],
+                outputs: vec![OutputType::P2tr],
+                postage: Some(546),
+            },
+            FeeRate::from_sat_per_vb_u32(2),
+        )
+        .unwrap();
+
+        assert_eq!(
+            estimate.deposit_amount,
+            (546 + estimate.mint_fee).div_ceil(2)
+        );
+        assert_eq!(estimate.total, 2 * estimate.deposit_amount);
+    }
+}
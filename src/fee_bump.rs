@@ -0,0 +1,415 @@
+use bitcoin::{
+    absolute, transaction, Amount, FeeRate, OutPoint, Psbt, Sequence, Transaction, TxOut,
+};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    add_funding_input, assert_sat_preserved, deserialize_psbt, first_sat_locations,
+    parse_mainnet_address, restore_labitbu_leaf,
+    weight::{predicted_vsize, with_predicted_witnesses},
+    Error, FundingInput, FundingKind, PsbtResult, SatLocation,
+};
+
+/// Relay policy's minimum feerate, also the incremental feerate a replacement
+/// has to add on top of the fee it replaces.
+pub(crate) const MIN_RELAY_FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_u32(1);
+
+/// Sets every input's sequence to signal BIP125 replaceability.
+pub fn signal_rbf(psbt: &mut Psbt) {
+    for txin in psbt.unsigned_tx.input.iter_mut() {
+        txin.sequence = Sequence::ENABLE_RBF_NO_LOCKTIME;
+    }
+}
+
+/// Rebuilds an unconfirmed mint at `fee_rate` by taking the extra fee out of
+/// output `fee_output`.
+///
+/// The replacement spends the same inputs and keeps every other output as is.
+/// Its fee is raised to at least the original fee plus the incremental relay
+/// fee for its size, as BIP125 requires. Signatures from the original are
+/// dropped since they no longer commit to the right outputs. `original` may
+/// already be finalized: the leaf data of its labitbu inputs is read back
+/// from their witnesses so the replacement can be signed again.
+pub fn build_replacement(
+    original: &Psbt,
+    fee_rate: FeeRate,
+    fee_output: usize,
+) -> Result<Psbt, Error> {
+    let old_fee = original.fee()?;
+    let vsize = predicted_vsize(original)?;
+
+    let target = fee_rate.fee_vb(vsize).ok_or(Error::FeeOverflow)?;
+    let minimum = old_fee + MIN_RELAY_FEE_RATE.fee_vb(vsize).ok_or(Error::FeeOverflow)?;
+    let extra = target.max(minimum) - old_fee;
+
+    let mut psbt = original.clone();
+    for psbt_in in psbt.inputs.iter_mut() {
+        restore_labitbu_leaf(psbt_in);
+    }
+    let output = psbt
+        .unsigned_tx
+        .output
+        .get_mut(fee_output)
+        .ok_or(Error::NoSuchOutput(fee_output))?;
+    output.value = output
+        .value
+        .checked_sub(extra)
+        .filter(|v| *v >= output.script_pubkey.minimal_non_dust())
+        .ok_or(Error::InsufficientFunds {
+            available: output.value,
+            required: extra + output.script_pubkey.minimal_non_dust(),
+        })?;
+
+    // Shrinking an output moves the boundaries of every output after it, so
+    // make sure no labitbu sat is pushed into a different output.
+    let before = first_sat_locations(original)?;
+    let after = first_sat_locations(&psbt)?;
+    for (input, psbt_in) in psbt.inputs.iter().enumerate() {
+        if psbt_in.tap_scripts.is_empty() {
+            continue;
+        }
+        let same_output = matches!(
+            (before[input], after[input]),
+            (SatLocation::Output { vout: a, .. }, SatLocation::Output { vout: b, .. }) if a == b
+        );
+        if !same_output {
+            return Err(Error::SatNotPreserved { input });
+        }
+    }
+
+    signal_rbf(&mut psbt);
+    for psbt_in in psbt.inputs.iter_mut() {
+        psbt_in.final_script_sig = None;
+        psbt_in.final_script_witness = None;
+        psbt_in.partial_sigs.clear();
+        psbt_in.tap_key_sig = None;
+        psbt_in.tap_script_sigs.clear();
+    }
+
+    Ok(psbt)
+}
+
+/// The combined feerate of a parent and a child paying for it.
+pub fn package_fee_rate(
+    parent_fee: Amount,
+    parent_vsize: u64,
+    child_fee: Amount,
+    child_vsize: u64,
+) -> FeeRate {
+    let weight = (parent_vsize + child_vsize) * 4;
+    FeeRate::from_sat_per_kwu((parent_fee + child_fee).to_sat() * 1000 / weight)
+}
+
+/// Builds a child that spends output `vout` of `parent` to accelerate it.
+///
+/// The parent output comes first so a labitbu sat on it stays at the start of
+/// the child's only output, which pays `destination_address`. Extra funding
+/// inputs can be added when the parent output is too small to pay for the
+/// whole package. The child fee is chosen so the package reaches `fee_rate`.
+pub fn build_cpfp_child(
+    parent: &Psbt,
+    vout: u32,
+    kind: FundingKind,
+    funding: Vec<FundingInput>,
+    destination_address: &str,
+    fee_rate: FeeRate,
+) -> Result<Psbt, Error> {
+    let destination = parse_mainnet_address(destination_address)?;
+    let parent_fee = parent.fee()?;
+    let parent_vsize = predicted_vsize(parent)?;
+    let parent_tx = with_predicted_witnesses(parent)?;
+    let prevout = parent_tx
+        .output
+        .get(vout as usize)
+        .cloned()
+        .ok_or(Error::NoSuchOutput(vout as usize))?;
+
+    let mut psbt = Psbt::from_unsigned_tx(Transaction {
+        version: transaction::Version(2),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: destination.script_pubkey(),
+        }],
+    })?;
+    add_funding_input(
+        &mut psbt,
+        FundingInput {
+            outpoint: OutPoint::new(parent_tx.compute_txid(), vout),
+            prevout,
+            kind,
+        },
+    )?;
+    for f in funding {
+        add_funding_input(&mut psbt, f)?;
+    }
+    signal_rbf(&mut psbt);
+
+    let child_vsize = predicted_vsize(&psbt)?;
+    let package_fee = fee_rate
+        .fee_vb(parent_vsize + child_vsize)
+        .ok_or(Error::FeeOverflow)?;
+    let child_fee = package_fee
+        .checked_sub(parent_fee)
+        .unwrap_or(Amount::ZERO)
+        .max(
+            MIN_RELAY_FEE_RATE
+                .fee_vb(child_vsize)
+                .ok_or(Error::FeeOverflow)?,
+        );
+
+    let available: Amount = psbt
+        .inputs
+        .iter()
+        .filter_map(|i| i.witness_utxo.as_ref())
+        .map(|o| o.value)
+        .sum();
+    let output = &mut psbt.unsigned_tx.output[0];
+    output.value = available
+        .checked_sub(child_fee)
+        .filter(|v| *v >= output.script_pubkey.minimal_non_dust())
+        .ok_or(Error::InsufficientFunds {
+            available,
+            required: child_fee + output.script_pubkey.minimal_non_dust(),
+        })?;
+    assert_sat_preserved(&psbt, 0, 0)?;
+
+    Ok(psbt)
+}
+
+fn fee_rate_from_js(sat_per_vb: u64) -> Result<FeeRate, JsValue> {
+    FeeRate::from_sat_per_vb(sat_per_vb).ok_or_else(|| JsValue::from_str("fee rate overflow"))
+}
+
+#[wasm_bindgen]
+pub fn signal_mint_rbf(psbt_bytes: Vec<u8>) -> Result<PsbtResult, JsValue> {
+    let mut psbt = deserialize_psbt(&psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    signal_rbf(&mut psbt);
+    Ok(psbt.into())
+}
+
+#[wasm_bindgen]
+pub fn bump_mint_fee(
+    psbt_bytes: Vec<u8>,
+    fee_rate_sat_vb: u64,
+    fee_output: usize,
+) -> Result<PsbtResult, JsValue> {
+    let psbt = deserialize_psbt(&psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let replacement = build_replacement(&psbt, fee_rate_from_js(fee_rate_sat_vb)?, fee_output)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    Ok(replacement.into())
+}
+
+#[wasm_bindgen]
+pub fn mint_cpfp_child(
+    parent_psbt_bytes: Vec<u8>,
+    vout: u32,
+    kind: JsValue,
+    funding: JsValue,
+    destination_address: String,
+    fee_rate_sat_vb: u64,
+) -> Result<PsbtResult, JsValue> {
+    let parent =
+        deserialize_psbt(&parent_psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let kind: FundingKind = serde_wasm_bindgen::from_value(kind)
+        .map_err(|e| JsValue::from_str(&format!("kind: {}", e)))?;
+    let funding: Vec<FundingInput> = serde_wasm_bindgen::from_value(funding)
+        .map_err(|e| JsValue::from_str(&format!("funding: {}", e)))?;
+
+    let child = build_cpfp_child(
+        &parent,
+        vout,
+        kind,
+        funding,
+        &destination_address,
+        fee_rate_from_js(fee_rate_sat_vb)?,
+    )
+    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(child.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        hashes::Hash, key::TweakedPublicKey, Address, Network, ScriptBuf, Txid, XOnlyPublicKey,
+    };
+
+    use bitcoin::Witness;
+
+    use crate::{
+        build_funded_mint, create_taproot_spend_info, finalize_labitbu_inputs, nums_from_tag,
+        sign_psbt_with, verify_finalized, Deposit,
+    };
+
+    fn p2tr_address(tag: &[u8]) -> String {
+        let key = TweakedPublicKey::dangerous_assume_tweaked(nums_from_tag(tag));
+        Address::p2tr_tweaked(key, Network::Bitcoin).to_string()
+    }
+
+    fn wallet_key() -> XOnlyPublicKey {
+        nums_from_tag(b"wallet")
+    }
+
+    fn funded_mint() -> Psbt {
+        let pubkey = nums_from_tag(b"depositor");
+        let payload = vec![1u8; 4096];
+        let spend_info = create_taproot_spend_info(pubkey, payload.clone()).unwrap();
+        let deposit = Deposit {
+            pubkey,
+            payload,
+            outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            prevout: TxOut {
+                value: Amount::from_sat(546),
+                script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+            },
+        };
+        let funding = FundingInput {
+            outpoint: OutPoint::new(Txid::all_zeros(), 1),
+            prevout: TxOut {
+                value: Amount::from_sat(20_000),
+                script_pubkey: ScriptBuf::new_p2tr(
+                    &secp256k1::Secp256k1::verification_only(),
+                    wallet_key(),
+                    None,
+                ),
+            },
+            kind: FundingKind::P2trKeyPath {
+                internal_key: wallet_key(),
+            },
+        };
+
+        build_funded_mint(
+            vec![deposit],
+            &p2tr_address(b"destination"),
+            vec![funding],
+            &wallet_address(),
+            FeeRate::from_sat_per_vb_u32(1),
+        )
+        .unwrap()
+    }
+
+    fn wallet_address() -> String {
+        let secp = secp256k1::Secp256k1::verification_only();
+        Address::p2tr(&secp, wallet_key(), None, Network::Bitcoin).to_string()
+    }
+
+    #[test]
+    fn replacement_pays_target_rate_from_change() {
+        let original = funded_mint();
+        let vsize = predicted_vsize(&original).unwrap();
+
+        let replacement = build_replacement(&original, FeeRate::from_sat_per_vb_u32(5), 1).unwrap();
+
+        assert_eq!(replacement.fee().unwrap(), Amount::from_sat(5 * vsize));
+        assert_eq!(
+            replacement.unsigned_tx.output[0].value,
+            Amount::from_sat(546)
+        );
+        assert!(replacement
+            .unsigned_tx
+            .input
+            .iter()
+            .all(|i| i.sequence.is_rbf()));
+    }
+
+    #[test]
+    fn finalized_mint_can_be_bumped_and_signed_again() {
+        let keypair =
+            secp256k1::Keypair::from_seckey_slice(&secp256k1::Secp256k1::new(), &[4u8; 32])
+                .unwrap();
+        let pubkey = keypair.x_only_public_key().0;
+        let payload = vec![3u8; 4096];
+        let spend_info = create_taproot_spend_info(pubkey, payload.clone()).unwrap();
+        let deposit = Deposit {
+            pubkey,
+            payload,
+            outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            prevout: TxOut {
+                value: Amount::from_sat(546),
+                script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+            },
+        };
+        let wallet = FundingInput {
+            outpoint: OutPoint::new(Txid::all_zeros(), 1),
+            prevout: TxOut {
+                value: Amount::from_sat(20_000),
+                script_pubkey: ScriptBuf::new_p2tr(
+                    &secp256k1::Secp256k1::verification_only(),
+                    pubkey,
+                    None,
+                ),
+            },
+            kind: FundingKind::P2trKeyPath {
+                internal_key: pubkey,
+            },
+        };
+        let sign = |psbt: &mut Psbt| {
+            assert_eq!(sign_psbt_with(psbt, &keypair).unwrap(), 2);
+            finalize_labitbu_inputs(psbt).unwrap();
+            let key_sig = psbt.inputs[1].tap_key_sig.take().unwrap();
+            psbt.inputs[1].final_script_witness = Some(Witness::from_slice(&[key_sig.to_vec()]));
+            verify_finalized(psbt).unwrap();
+        };
+
+        let mut original = build_funded_mint(
+            vec![deposit],
+            &p2tr_address(b"destination"),
+            vec![wallet],
+            &p2tr_address(b"change"),
+            FeeRate::from_sat_per_vb_u32(1),
+        )
+        .unwrap();
+        sign(&mut original);
+        assert!(original.inputs[0].tap_scripts.is_empty());
+
+        let mut replacement =
+            build_replacement(&original, FeeRate::from_sat_per_vb_u32(5), 1).unwrap();
+        assert!(replacement.inputs[0].final_script_witness.is_none());
+        sign(&mut replacement);
+        assert!(replacement.fee().unwrap() > original.fee().unwrap());
+    }
+
+    #[test]
+    fn replacement_adds_at_least_the_incremental_fee() {
+        let original = funded_mint();
+        let vsize = predicted_vsize(&original).unwrap();
+
+        let replacement = build_replacement(&original, FeeRate::from_sat_per_vb_u32(0), 1).unwrap();
+
+        assert_eq!(
+            replacement.fee().unwrap(),
+            original.fee().unwrap() + Amount::from_sat(vsize)
+        );
+    }
+
+    #[test]
+    fn cpfp_child_brings_package_to_target_rate() {
+        let parent = funded_mint();
+        let parent_vsize = predicted_vsize(&parent).unwrap();
+        let target = FeeRate::from_sat_per_vb_u32(10);
+
+        let child = build_cpfp_child(
+            &parent,
+            1,
+            FundingKind::P2trKeyPath {
+                internal_key: wallet_key(),
+            },
+            vec![],
+            &wallet_address(),
+            target,
+        )
+        .unwrap();
+
+        let child_vsize = predicted_vsize(&child).unwrap();
+        let rate = package_fee_rate(
+            parent.fee().unwrap(),
+            parent_vsize,
+            child.fee().unwrap(),
+            child_vsize,
+        );
+        assert_eq!(rate.to_sat_per_vb_floor(), 10);
+    }
+}
@@ -0,0 +1,382 @@
+use bitcoin::{
+    absolute, psbt, transaction, Amount, CompressedPublicKey, FeeRate, OutPoint, Psbt, ScriptBuf,
+    TapSighashType, Transaction, TxIn, TxOut, XOnlyPublicKey,
+};
+use secp256k1::{PublicKey, Secp256k1};
+use serde::Deserialize;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    add_labitbu_leaf, assert_sat_preserved, create_taproot_spend_info, parse_mainnet_address,
+    weight::predicted_vsize, Deposit, Error, PsbtResult,
+};
+
+/// How a fee-paying input is spent.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FundingKind {
+    P2wpkh {
+        pubkey: PublicKey,
+    },
+    P2trKeyPath {
+        internal_key: XOnlyPublicKey,
+    },
+    P2shP2wpkh {
+        pubkey: PublicKey,
+    },
+    /// A pay-to-anchor output, spendable by anyone with an empty witness.
+    PayToAnchor,
+}
+
+/// A wallet UTXO used to pay fees alongside labitbu deposits.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FundingInput {
+    pub outpoint: OutPoint,
+    pub prevout: TxOut,
+    pub kind: FundingKind,
+}
+
+impl FundingInput {
+    /// The script_pubkey this input's key commits to.
+    pub(crate) fn expected_script_pubkey(&self) -> ScriptBuf {
+        match &self.kind {
+            FundingKind::P2wpkh { pubkey } => {
+                ScriptBuf::new_p2wpkh(&CompressedPublicKey(*pubkey).wpubkey_hash())
+            }
+            FundingKind::P2trKeyPath { internal_key } => {
+                ScriptBuf::new_p2tr(&Secp256k1::verification_only(), *internal_key, None)
+            }
+            FundingKind::P2shP2wpkh { pubkey } => {
+                let redeem_script =
+                    ScriptBuf::new_p2wpkh(&CompressedPublicKey(*pubkey).wpubkey_hash());
+                ScriptBuf::new_p2sh(&redeem_script.script_hash())
+            }
+            FundingKind::PayToAnchor => ScriptBuf::new_p2a(),
+        }
+    }
+}
+
+/// Appends a wallet input to `psbt`, filling in the fields a signer needs for
+/// its script type.
+pub fn add_funding_input(psbt: &mut Psbt, funding: FundingInput) -> Result<(), Error> {
+    if funding.prevout.script_pubkey != funding.expected_script_pubkey() {
+        return Err(Error::FundingScriptMismatch(funding.outpoint));
+    }
+
+    let mut psbt_in = psbt::Input {
+        witness_utxo: Some(funding.prevout),
+        ..Default::default()
+    };
+    match funding.kind {
+        FundingKind::P2wpkh { .. } => {
+            psbt_in.sighash_type = Some(bitcoin::EcdsaSighashType::All.into());
+        }
+        FundingKind::P2trKeyPath { internal_key } => {
+            psbt_in.tap_internal_key = Some(internal_key);
+            psbt_in.sighash_type = Some(TapSighashType::Default.into());
+        }
+        FundingKind::P2shP2wpkh { pubkey } => {
+            psbt_in.redeem_script = Some(ScriptBuf::new_p2wpkh(
+                &CompressedPublicKey(pubkey).wpubkey_hash(),
+            ));
+            psbt_in.sighash_type = Some(bitcoin::EcdsaSighashType::All.into());
+        }
+        FundingKind::PayToAnchor => {}
+    }
+
+    psbt.unsigned_tx.input.push(TxIn {
+        previous_output: funding.outpoint,
+        ..Default::default()
+    });
+    psbt.inputs.push(psbt_in);
+
+    Ok(())
+}
+
+/// Refuses an output worth less than the dust limit of its script.
+pub(crate) fn check_dust(vout: usize, output: &TxOut) -> Result<(), Error> {
+    let threshold = output.script_pubkey.minimal_non_dust();
+    if output.value < threshold {
+        return Err(Error::DustOutput {
+            vout,
+            value: output.value,
+            threshold,
+        });
+    }
+    Ok(())
+}
+
+/// Sets the value of `psbt`'s last output, a change placeholder, to whatever
+/// is left once `fee_rate` is paid on the signed size. The placeholder is
+/// dropped if nothing is left, and change below the dust limit is refused
+/// rather than quietly added to the fee.
+pub(crate) fn settle_change(psbt: &mut Psbt, fee_rate: FeeRate) -> Result<(), Error> {
+    let fee = fee_rate
+        .fee_vb(predicted_vsize(psbt)?)
+        .ok_or(Error::FeeOverflow)?;
+    let available: Amount = psbt
+        .inputs
+        .iter()
+        .filter_map(|i| i.witness_utxo.as_ref())
+        .map(|o| o.value)
+        .sum();
+    let spent: Amount = psbt.unsigned_tx.output.iter().map(|o| o.value).sum();
+    let change = available
+        .checked_sub(spent + fee)
+        .ok_or(Error::InsufficientFunds {
+            available,
+            required: spent + fee,
+        })?;
+
+    if change == Amount::ZERO {
+        psbt.unsigned_tx.output.pop();
+        psbt.outputs.pop();
+        return Ok(());
+    }
+    let vout = psbt.unsigned_tx.output.len() - 1;
+    let change_output = &mut psbt.unsigned_tx.output[vout];
+    change_output.value = change;
+    check_dust(vout, change_output)
+}
+
+/// Builds a mint whose fee is paid by wallet inputs rather than the deposits.
+///
+/// Deposits come first so the labitbu sat stays at the start of output 0,
+/// which receives the full deposited amount. A deposit below the
+/// destination's dust limit, such as one stuck for being too small to mint on
+/// its own, is topped up to that limit from the funding inputs. Funding
+/// inputs follow, `fee_rate` is paid on the signed size and any change goes
+/// to `change_address` as output 1; change below the dust limit is refused.
+pub fn build_funded_mint(
+    deposits: Vec<Deposit>,
+    destination_address: &str,
+    funding: Vec<FundingInput>,
+    change_address: &str,
+    fee_rate: FeeRate,
+) -> Result<Psbt, Error> {
+    if deposits.is_empty() {
+        return Err(Error::NoDeposits);
+    }
+    let destination = parse_mainnet_address(destination_address)?.script_pubkey();
+    let change_script = parse_mainnet_address(change_address)?.script_pubkey();
+
+    let deposited: Amount = deposits.iter().map(|d| d.prevout.value).sum();
+    let postage = deposited.max(destination.minimal_non_dust());
+    let outputs = vec![
+        TxOut {
+            value: postage,
+            script_pubkey: destination,
+        },
+        TxOut {
+            value: Amount::ZERO,
+            script_pubkey: change_script,
+        },
+    ];
+
+    let unsigned_tx = Transaction {
+        version: transaction::Version(2),
+        lock_time: absolute::LockTime::ZERO,
+        input: deposits
+            .iter()
+            .map(|d| TxIn {
+                previous_output: d.outpoint,
+                ..Default::default()
+            })
+            .collect(),
+        output: outputs,
+    };
+    let mut psbt = Psbt::from_unsigned_tx(unsigned_tx)?;
+
+    for (psbt_in, d) in psbt.inputs.iter_mut().zip(deposits) {
+        let taproot_spend_info = create_taproot_spend_info(d.pubkey, d.payload)?;
+        psbt_in.witness_utxo = Some(d.prevout);
+        add_labitbu_leaf(psbt_in, d.pubkey, &taproot_spend_info);
+    }
+    for f in funding {
+        add_funding_input(&mut psbt, f)?;
+    }
+    settle_change(&mut psbt, fee_rate)?;
+    for (vout, output) in psbt.unsigned_tx.output.iter().enumerate() {
+        check_dust(vout, output)?;
+    }
+    assert_sat_preserved(&psbt, 0, 0)?;
+
+    Ok(psbt)
+}
+
+#[wasm_bindgen]
+pub fn mint_with_funding(
+    deposits: JsValue,
+    destination_address: String,
+    funding: JsValue,
+    change_address: String,
+    fee_rate_sat_vb: u64,
+) -> Result<PsbtResult, JsValue> {
+    let deposits: Vec<Deposit> = serde_wasm_bindgen::from_value(deposits)
+        .map_err(|e| JsValue::from_str(&format!("deposits: {}", e)))?;
+    let funding: Vec<FundingInput> = serde_wasm_bindgen::from_value(funding)
+        .map_err(|e| JsValue::from_str(&format!("funding: {}", e)))?;
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb)
+        .ok_or_else(|| JsValue::from_str("fee rate overflow"))?;
+
+    let psbt = build_funded_mint(
+        deposits,
+        &destination_address,
+        funding,
+        &change_address,
+        fee_rate,
+    )
+    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{hashes::Hash, key::TweakedPublicKey, Address, Network, Txid};
+    use secp256k1::SecretKey;
+
+    use crate::nums_from_tag;
+
+    fn p2tr_address(tag: &[u8]) -> String {
+        let key = TweakedPublicKey::dangerous_assume_tweaked(nums_from_tag(tag));
+        Address::p2tr_tweaked(key, Network::Bitcoin).to_string()
+    }
+
+    fn wallet_pubkey() -> PublicKey {
+        let sk = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        PublicKey::from_secret_key(&Secp256k1::new(), &sk)
+    }
+
+    fn deposit() -> Deposit {
+        let pubkey = nums_from_tag(b"depositor");
+        let payload = vec![1u8; 64];
+        let spend_info = create_taproot_spend_info(pubkey, payload.clone()).unwrap();
+        Deposit {
+            pubkey,
+            payload,
+            outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            prevout: TxOut {
+                value: Amount::from_sat(546),
+                script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+            },
+        }
+    }
+
+    fn funding(kind: FundingKind, vout: u32) -> FundingInput {
+        let mut input = FundingInput {
+            outpoint: OutPoint::new(Txid::all_zeros(), vout),
+            prevout: TxOut {
+                value: Amount::from_sat(5_000),
+                script_pubkey: ScriptBuf::new(),
+            },
+            kind,
+        };
+        input.prevout.script_pubkey = input.expected_script_pubkey();
+        input
+    }
+
+    #[test]
+    fn funded_mint_sets_fields_per_input_type() {
+        let pubkey = wallet_pubkey();
+        let funding = vec![
+            funding(FundingKind::P2wpkh { pubkey }, 1),
+            funding(
+                FundingKind::P2trKeyPath {
+                    internal_key: pubkey.x_only_public_key().0,
+                },
+                2,
+            ),
+            funding(FundingKind::P2shP2wpkh { pubkey }, 3),
+        ];
+
+        let psbt = build_funded_mint(
+            vec![deposit()],
+            &p2tr_address(b"destination"),
+            funding,
+            &p2tr_address(b"change"),
+            FeeRate::from_sat_per_vb_u32(2),
+        )
+        .unwrap();
+
+        let fee = psbt.fee().unwrap();
+        assert_eq!(fee, Amount::from_sat(2 * predicted_vsize(&psbt).unwrap()));
+        assert_eq!(psbt.unsigned_tx.output[0].value, Amount::from_sat(546));
+        assert_eq!(
+            psbt.unsigned_tx.output[1].value,
+            Amount::from_sat(15_000) - fee
+        );
+        assert_eq!(psbt.inputs.len(), 4);
+        assert!(!psbt.inputs[0].tap_scripts.is_empty());
+        assert!(psbt.inputs[1].redeem_script.is_none());
+        assert!(psbt.inputs[1].tap_internal_key.is_none());
+        assert_eq!(
+            psbt.inputs[2].tap_internal_key,
+            Some(pubkey.x_only_public_key().0)
+        );
+        assert!(psbt.inputs[3].redeem_script.as_ref().unwrap().is_p2wpkh());
+    }
+
+    #[test]
+    fn funded_mint_tops_up_dust_postage_and_refuses_dust_change() {
+        let mint = |deposit: Deposit, wallet: u64| {
+            let mut wallet_input = funding(
+                FundingKind::P2wpkh {
+                    pubkey: wallet_pubkey(),
+                },
+                1,
+            );
+            wallet_input.prevout.value = Amount::from_sat(wallet);
+            build_funded_mint(
+                vec![deposit],
+                &p2tr_address(b"destination"),
+                vec![wallet_input],
+                &p2tr_address(b"change"),
+                FeeRate::from_sat_per_vb_u32(1),
+            )
+        };
+
+        // A deposit too small to mint on its own still becomes a labitbu.
+        let mut stuck = deposit();
+        stuck.prevout.value = Amount::from_sat(100);
+        let psbt = mint(stuck, 5_000).unwrap();
+        let fee = psbt.fee().unwrap();
+        assert_eq!(psbt.unsigned_tx.output[0].value, Amount::from_sat(330));
+        assert_eq!(
+            psbt.unsigned_tx.output[1].value,
+            Amount::from_sat(5_000 - 230) - fee
+        );
+
+        let fee = mint(deposit(), 5_000).unwrap().fee().unwrap().to_sat();
+        assert!(matches!(
+            mint(deposit(), fee + 100),
+            Err(Error::DustOutput { vout: 1, .. })
+        ));
+        assert_eq!(mint(deposit(), fee).unwrap().unsigned_tx.output.len(), 1);
+    }
+
+    #[test]
+    fn funding_input_must_match_its_key() {
+        let mut input = funding(
+            FundingKind::P2wpkh {
+                pubkey: wallet_pubkey(),
+            },
+            1,
+        );
+        input.prevout.script_pubkey = ScriptBuf::new_p2a();
+
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version(2),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        })
+        .unwrap();
+
+        assert!(matches!(
+            add_funding_input(&mut psbt, input),
+            Err(Error::FundingScriptMismatch(_))
+        ));
+    }
+}
@@ -0,0 +1,208 @@
+use bitcoin::{Address, Psbt};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{predicted_vsize, LabitbuTraits, PsbtV2, ACCESSORY_NAMES, BASE_NAMES};
+
+/// A PSBT handed back to JS, in every encoding the front end needs.
+#[wasm_bindgen]
+pub struct PsbtResult {
+    psbt: Psbt,
+}
+
+#[wasm_bindgen]
+impl PsbtResult {
+    /// The serialized PSBT, for passing back into the other bindings.
+    #[wasm_bindgen(getter)]
+    pub fn bytes(&self) -> Vec<u8> {
+        self.psbt.serialize()
+    }
+
+    /// The PSBT in the BIP370 version 2 format, for building it up with
+    /// other parties.
+    #[wasm_bindgen(getter)]
+    pub fn bytes_v2(&self) -> Vec<u8> {
+        PsbtV2::new(self.psbt.clone()).serialize()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn base64(&self) -> String {
+        self.psbt.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn hex(&self) -> String {
+        self.psbt.serialize_hex()
+    }
+
+    /// The fee in sats, if every input's UTXO is known.
+    #[wasm_bindgen(getter)]
+    pub fn fee(&self) -> Option<u64> {
+        self.psbt.fee().ok().map(|fee| fee.to_sat())
+    }
+
+    /// The virtual size once signed, if every input's spend can be predicted.
+    #[wasm_bindgen(getter)]
+    pub fn vsize(&self) -> Option<u64> {
+        predicted_vsize(&self.psbt).ok()
+    }
+}
+
+impl From<Psbt> for PsbtResult {
+    fn from(psbt: Psbt) -> Self {
+        PsbtResult { psbt }
+    }
+}
+
+/// A labitbu deposit address.
+#[wasm_bindgen]
+pub struct DepositAddress {
+    address: Address,
+}
+
+#[wasm_bindgen]
+impl DepositAddress {
+    #[wasm_bindgen(getter)]
+    pub fn address(&self) -> String {
+        self.address.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn script_pubkey(&self) -> String {
+        self.address.script_pubkey().to_hex_string()
+    }
+}
+
+impl From<Address> for DepositAddress {
+    fn from(address: Address) -> Self {
+        DepositAddress { address }
+    }
+}
+
+/// A generated labitbu: the padded payload and the traits it was drawn with.
+#[wasm_bindgen]
+pub struct LabitbuImage {
+    payload: Vec<u8>,
+    traits: LabitbuTraits,
+}
+
+impl LabitbuImage {
+    pub(crate) fn new(payload: Vec<u8>, traits: LabitbuTraits) -> Self {
+        LabitbuImage { payload, traits }
+    }
+}
+
+#[wasm_bindgen]
+impl LabitbuImage {
+    /// The 4096-byte payload to commit to in the deposit address.
+    #[wasm_bindgen(getter)]
+    pub fn payload(&self) -> Vec<u8> {
+        self.payload.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn base(&self) -> usize {
+        self.traits.base
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn base_name(&self) -> Option<String> {
+        BASE_NAMES.get(self.traits.base).map(|s| s.to_string())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn accessory(&self) -> Option<usize> {
+        self.traits.accessory
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn accessory_name(&self) -> Option<String> {
+        self.traits
+            .accessory
+            .and_then(|i| ACCESSORY_NAMES.get(i))
+            .map(|s| s.to_string())
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn hue_shift(&self) -> u32 {
+        self.traits.hue_shift
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sleepy(&self) -> bool {
+        self.traits.sleepy
+    }
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &'static str = r#"
+export interface TaprootCommitment {
+  address: string;
+  script_pubkey: string;
+  output_key: string;
+  parity: number;
+  internal_key: string;
+  tweak: string;
+  merkle_root: string;
+  leaf_hash: string;
+  control_block: string;
+}
+
+export interface SpendInfoBundle {
+  descriptor: string;
+  internal_key: string;
+  leaf_script: string;
+  leaf_version: number;
+  control_block: string;
+  payload_sha256: string;
+}
+
+export interface OwnershipProof {
+  labitbu: { id: number; txid: string };
+  address: string;
+  statement: string;
+  signature: string;
+}
+
+export interface VanityMatch {
+  pubkey: string;
+  origin: [string, string] | null;
+  secret_key: string | null;
+  traits: { base: number; accessory: number | null; hue_shift: number; sleepy: boolean };
+}
+
+export interface MintEstimate {
+  deposit_amount: number;
+  mint_vsize: number;
+  mint_fee: number;
+  postage: number;
+  total: number;
+}
+
+export type PolicyViolation =
+  | { type: "non_standard_version"; version: number }
+  | { type: "too_heavy"; weight: number; max: number }
+  | { type: "too_small"; size: number; min: number }
+  | { type: "truc_too_large"; vsize: number; max: number }
+  | { type: "non_standard_output"; vout: number }
+  | { type: "op_return_too_large"; vout: number; size: number; max: number }
+  | { type: "multiple_op_returns"; count: number }
+  | { type: "dust"; vout: number; value: number; threshold: number }
+  | { type: "script_sig_too_large"; input: number; size: number }
+  | { type: "script_sig_not_push_only"; input: number }
+  | { type: "tapscript_stack_item_too_large"; input: number; item: number; size: number }
+  | { type: "control_block_too_large"; input: number; size: number }
+  | { type: "below_min_relay_fee"; fee: number; required: number }
+  | { type: "negative_fee"; inputs: number; outputs: number };
+
+export type SatRange = [number, number];
+
+export type SatWarning =
+  | { type: "missing"; sat: number }
+  | { type: "lands_in_fee"; sat: number }
+  | { type: "lands_in_change"; sat: number; vout: number };
+
+export interface SatFlowReport {
+  flow: { outputs: SatRange[][]; fee: SatRange[] };
+  warnings: SatWarning[];
+}
+"#;
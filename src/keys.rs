@@ -0,0 +1,277 @@
+use std::str::FromStr;
+
+use bitcoin::{
+    address::NetworkUnchecked,
+    bech32,
+    bip32::{DerivationPath, Fingerprint, KeySource, Xpub},
+    psbt,
+    taproot::{LeafVersion, TapLeafHash},
+    Address, AddressType, Network, NetworkKind, XOnlyPublicKey,
+};
+use secp256k1::{PublicKey, Secp256k1};
+
+use crate::{descriptor_checksum, spend_script, Error};
+
+/// A labitbu key, with where it was derived from when it came from an xpub.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LabitbuKey {
+    pub pubkey: XOnlyPublicKey,
+    pub origin: Option<KeySource>,
+}
+
+/// Parses the key a labitbu is generated and deposited for.
+///
+/// Accepts a 32-byte x-only or 33-byte compressed key in hex, a taproot
+/// address (its output key), a nostr `npub`, or a descriptor key expression: an
+/// xpub followed by an unhardened derivation path, optionally preceded by a
+/// `[fingerprint/path]` origin and optionally wrapped in `tr(...)` with a
+/// checksum, which must match. Without an origin, one is only recorded when
+/// the xpub is a master key, since otherwise its master's fingerprint is
+/// unknown.
+pub fn parse_labitbu_key(input: &str) -> Result<LabitbuKey, Error> {
+    let mut key = input.trim();
+    if let Some((descriptor, checksum)) = key.split_once('#') {
+        if descriptor_checksum(descriptor).as_deref() != Some(checksum) {
+            return Err(Error::InvalidKey("descriptor checksum mismatch"));
+        }
+        key = descriptor;
+    }
+    if let Some(inner) = key.strip_prefix("tr(").and_then(|k| k.strip_suffix(')')) {
+        if inner.contains(',') {
+            return Err(Error::InvalidKey("script trees are not supported"));
+        }
+        key = inner;
+    }
+
+    if let Some(pubkey) = parse_plain_key(key)? {
+        return Ok(LabitbuKey {
+            pubkey,
+            origin: None,
+        });
+    }
+
+    let (xpub, origin) = parse_xpub_expression(key)?;
+
+    Ok(LabitbuKey {
+        pubkey: xpub.to_x_only_pub(),
+        origin,
+    })
+}
+
+/// Parses `[fingerprint/path]xpub/path` into the xpub at the end of the path
+/// and its origin. The origin is optional; without it, a master xpub is its
+/// own origin and any other xpub has none.
+pub(crate) fn parse_xpub_expression(key: &str) -> Result<(Xpub, Option<KeySource>), Error> {
+    let mut key = key;
+    let mut origin = None;
+    if let Some(rest) = key.strip_prefix('[') {
+        let (origin_str, rest) = rest
+            .split_once(']')
+            .ok_or(Error::InvalidKey("unterminated key origin"))?;
+        let (fingerprint, path) = origin_str.split_once('/').unwrap_or((origin_str, ""));
+        let fingerprint = Fingerprint::from_str(fingerprint)
+            .map_err(|_| Error::InvalidKey("invalid origin fingerprint"))?;
+        origin = Some((fingerprint, parse_path(path)?));
+        key = rest;
+    }
+
+    let (xpub, path) = key.split_once('/').unwrap_or((key, ""));
+    let xpub = Xpub::from_str(xpub)?;
+    if xpub.network != NetworkKind::Main {
+        return Err(Error::InvalidKey("not a mainnet xpub"));
+    }
+    if path.contains('*') {
+        return Err(Error::InvalidKey("wildcard needs a concrete index"));
+    }
+    let path = parse_path(path)?;
+    let derived = xpub.derive_pub(&Secp256k1::verification_only(), &path)?;
+
+    let origin = match origin {
+        Some((fingerprint, origin_path)) => Some((fingerprint, origin_path.extend(&path))),
+        None if xpub.depth == 0 => Some((xpub.fingerprint(), path)),
+        None => None,
+    };
+
+    Ok((derived, origin))
+}
+
+/// Parses a key given without derivation info: x-only or compressed hex, a
+/// taproot address or a nostr `npub`. Compressed keys lose their parity, so
+/// both parities of a point give the same labitbu.
+///
+/// Returns `None` if `key` is none of these formats.
+fn parse_plain_key(key: &str) -> Result<Option<XOnlyPublicKey>, Error> {
+    let is_hex = key.bytes().all(|b| b.is_ascii_hexdigit());
+    if is_hex && key.len() == 64 {
+        return XOnlyPublicKey::from_str(key)
+            .map(Some)
+            .map_err(|_| Error::InvalidKey("not a valid curve point"));
+    }
+    if is_hex && key.len() == 66 {
+        return PublicKey::from_str(key)
+            .map(|pk| Some(pk.x_only_public_key().0))
+            .map_err(|_| Error::InvalidKey("not a valid curve point"));
+    }
+    if key.starts_with("npub1") {
+        let (hrp, data) =
+            bech32::decode(key).map_err(|_| Error::InvalidKey("invalid npub encoding"))?;
+        if hrp.as_str() != "npub" || data.len() != 32 {
+            return Err(Error::InvalidKey("invalid npub encoding"));
+        }
+        return XOnlyPublicKey::from_slice(&data)
+            .map(Some)
+            .map_err(|_| Error::InvalidKey("not a valid curve point"));
+    }
+    if let Ok(address) = key.parse::<Address<NetworkUnchecked>>() {
+        let address = address.require_network(Network::Bitcoin)?;
+        if address.address_type() != Some(AddressType::P2tr) {
+            return Err(Error::InvalidKey("not a taproot address"));
+        }
+        let program = address.witness_program().expect("taproot has a program");
+        return XOnlyPublicKey::from_slice(program.program().as_bytes())
+            .map(Some)
+            .map_err(|_| Error::InvalidKey("not a valid curve point"));
+    }
+
+    Ok(None)
+}
+
+/// Parses a `/`-separated derivation path with no leading `m`.
+fn parse_path(path: &str) -> Result<DerivationPath, Error> {
+    if path.is_empty() {
+        return Ok(DerivationPath::master());
+    }
+    Ok(DerivationPath::from_str(&format!("m/{}", path))?)
+}
+
+/// Deserializes a labitbu key in any format [`parse_labitbu_key`] accepts.
+pub(crate) fn deserialize_labitbu_pubkey<'de, D>(
+    deserializer: D,
+) -> Result<XOnlyPublicKey, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let key = <String as serde::Deserialize>::deserialize(deserializer)?;
+    parse_labitbu_key(&key)
+        .map(|key| key.pubkey)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Records where `key` came from on a labitbu input, so a hardware wallet
+/// holding the xpub recognizes the leaf as its own.
+pub fn add_key_origin(psbt_in: &mut psbt::Input, key: &LabitbuKey) {
+    if let Some(origin) = &key.origin {
+        let leaf_hash = TapLeafHash::from_script(&spend_script(key.pubkey), LeafVersion::TapScript);
+        psbt_in
+            .tap_key_origins
+            .insert(key.pubkey, (vec![leaf_hash], origin.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::bip32::Xpriv;
+
+    fn xpub() -> (Xpriv, Xpub) {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Bitcoin, &[1u8; 32]).unwrap();
+        let account = master
+            .derive_priv(&secp, &DerivationPath::from_str("m/86'/0'/0'").unwrap())
+            .unwrap();
+        (master, Xpub::from_priv(&secp, &account))
+    }
+
+    #[test]
+    fn xpub_with_origin_derives_and_records_path() {
+        let secp = Secp256k1::new();
+        let (master, account) = xpub();
+        let fingerprint = master.fingerprint(&secp);
+
+        let key =
+            parse_labitbu_key(&format!("tr([{}/86'/0'/0']{}/0/5)", fingerprint, account)).unwrap();
+
+        let expected = master
+            .derive_priv(&secp, &DerivationPath::from_str("m/86'/0'/0'/0/5").unwrap())
+            .unwrap()
+            .to_keypair(&secp)
+            .x_only_public_key()
+            .0;
+        assert_eq!(key.pubkey, expected);
+        assert_eq!(
+            key.origin,
+            Some((
+                fingerprint,
+                DerivationPath::from_str("m/86'/0'/0'/0/5").unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn plain_keys_and_bad_paths() {
+        let hex_key = crate::nums_from_tag(b"depositor").to_string();
+        let key = parse_labitbu_key(&hex_key).unwrap();
+        assert_eq!(key.pubkey.to_string(), hex_key);
+        assert_eq!(key.origin, None);
+
+        let point = crate::nums_from_tag(b"depositor");
+        let even = PublicKey::from_x_only_public_key(point, secp256k1::Parity::Even);
+        let odd = PublicKey::from_x_only_public_key(point, secp256k1::Parity::Odd);
+        assert_eq!(parse_labitbu_key(&even.to_string()).unwrap().pubkey, point);
+        assert_eq!(parse_labitbu_key(&odd.to_string()).unwrap().pubkey, point);
+        let deposit_key: Result<_, serde::de::value::Error> = deserialize_labitbu_pubkey(
+            serde::de::IntoDeserializer::into_deserializer(odd.to_string().as_str()),
+        );
+        assert_eq!(deposit_key.unwrap(), point);
+
+        let address = Address::p2tr(
+            &Secp256k1::verification_only(),
+            point,
+            None,
+            Network::Bitcoin,
+        );
+        let output_key = address.witness_program().unwrap();
+        assert_eq!(
+            parse_labitbu_key(&address.to_string())
+                .unwrap()
+                .pubkey
+                .serialize(),
+            output_key.program().as_bytes()
+        );
+
+        let npub = "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg";
+        assert_eq!(
+            parse_labitbu_key(npub).unwrap().pubkey.to_string(),
+            "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e"
+        );
+
+        assert!(matches!(
+            parse_labitbu_key(&"ff".repeat(32)),
+            Err(Error::InvalidKey("not a valid curve point"))
+        ));
+
+        let (_, account) = xpub();
+        assert_eq!(
+            parse_labitbu_key(&format!("{}/0/5", account))
+                .unwrap()
+                .origin,
+            None
+        );
+        let descriptor = format!("tr({}/0/5)", account);
+        let checksum = crate::descriptor_checksum(&descriptor).unwrap();
+        assert!(parse_labitbu_key(&format!("{}#{}", descriptor, checksum)).is_ok());
+        let mut typo = checksum.into_bytes();
+        typo[0] = if typo[0] == b'q' { b'p' } else { b'q' };
+        assert!(matches!(
+            parse_labitbu_key(&format!(
+                "{}#{}",
+                descriptor,
+                String::from_utf8(typo).unwrap()
+            )),
+            Err(Error::InvalidKey("descriptor checksum mismatch"))
+        ));
+
+        assert!(parse_labitbu_key(&format!("{}/0/*", account)).is_err());
+        assert!(parse_labitbu_key(&format!("{}/0'/1", account)).is_err());
+    }
+}
@@ -4,49 +4,212 @@ use bitcoin::{
     absolute,
     hashes::{sha256, Hash, HashEngine},
     opcodes::all::OP_CHECKSIG,
+    psbt,
     script::Builder,
     taproot::{LeafVersion, NodeInfo, TaprootSpendInfo},
-    Address, Amount, Network, Psbt, ScriptBuf, TapNodeHash, TapSighashType, Transaction, TxIn,
-    TxOut, XOnlyPublicKey,
+    Address, Amount, Network, OutPoint, Psbt, ScriptBuf, TapNodeHash, TapSighashType, Transaction,
+    TxIn, TxOut, XOnlyPublicKey,
 };
 use image_webp::{ColorType, EncoderParams, WebPEncoder};
 use rand::{rngs::SmallRng, RngCore, SeedableRng};
 use secp256k1::Secp256k1;
+use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::{self, from_value};
 use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
 
-use hex::FromHex;
 use image::{imageops, RgbaImage};
 
+mod batch;
+mod bip322;
+mod commitment;
+mod descriptor;
+mod ephemeral;
+mod error;
+mod estimate;
+mod fee_bump;
+mod funding;
+mod js;
+mod keys;
+mod listing;
+mod mnemonic;
+mod musig;
+mod policy;
+mod psbt_v2;
+mod recovery;
+mod sat_flow;
+mod signer;
+mod signing;
+mod transfer;
+mod truc;
+mod vanity;
+mod verify;
+mod weight;
+
+pub use batch::{build_batch_mint, mint_batch, BatchDeposit};
+pub use bip322::{
+    labitbu_ownership_from_signature, labitbu_ownership_message, ownership_message,
+    ownership_proof_from_signature, prove_labitbu_ownership, prove_ownership, sign_bip322,
+    sign_message_key_path, sign_message_labitbu_leaf, verify_bip322, verify_labitbu_ownership,
+    verify_message, verify_ownership, Bip322Format, LabitbuRecord, OwnershipProof,
+};
+pub use commitment::{deposit_commitment, taproot_commitment, TaprootCommitment};
+pub use descriptor::{
+    deposit_descriptor, deposit_spend_info, deposit_watch_descriptor, descriptor_checksum,
+    spend_info_bundle, SpendInfoBundle,
+};
+pub use ephemeral::{
+    decrypt_backup, encrypt_backup, sign_and_finalize_labitbu_inputs, EphemeralKey,
+    DEFAULT_SCRYPT_LOG_N,
+};
+pub use error::Error;
+pub use estimate::{
+    estimate_mint, estimate_mint_cost, InputType, MintEstimate, MintShape, OutputType,
+};
+pub use fee_bump::{
+    build_cpfp_child, build_replacement, bump_mint_fee, mint_cpfp_child, package_fee_rate,
+    signal_mint_rbf, signal_rbf,
+};
+pub use funding::{
+    add_funding_input, build_funded_mint, mint_with_funding, FundingInput, FundingKind,
+};
+use funding::{check_dust, settle_change};
+pub use js::{DepositAddress, LabitbuImage, PsbtResult};
+pub use keys::{add_key_origin, parse_labitbu_key, LabitbuKey};
+pub use listing::{
+    build_listing, buy_listing, check_listing_sat, complete_listing, create_listing,
+    LISTING_PADDING_INPUTS,
+};
+pub use mnemonic::{
+    generate_mnemonic, generate_mnemonic_words, mnemonic_keypair, LABITBU_ACCOUNT, LABITBU_KEY_PATH,
+};
+pub use musig::{
+    combine_labitbu_signature, key_sort, musig_aggregate_key, musig_combine, nonce_agg, nonce_gen,
+    AggNonce, KeyAggContext, MusigSigner, PartialSig, PubNonce, SecNonce, SigningSession,
+};
+pub use policy::{
+    check_mint_policy, check_policy, check_psbt_policy, PolicyViolation, MAX_OP_RETURN_RELAY,
+    MAX_STANDARD_SCRIPTSIG_SIZE, MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE,
+    MIN_STANDARD_TX_NONWITNESS_SIZE,
+};
+pub use psbt_v2::{append_psbt_v2, deserialize_psbt, psbt_from_v2, psbt_to_v2, PsbtV2};
+pub use recovery::{
+    build_recovery, candidate_payloads, find_deposits, recover_deposit, LabitbuAssets, Utxo,
+};
+pub use sat_flow::{
+    assert_sat_preserved, check_labitbu_sats, first_sat_locations, sat_flow, trace_sats, SatFlow,
+    SatLocation, SatRange, SatWarning,
+};
+use signer::sign_built_psbt;
+pub use signer::{sign_psbt_with, JsSigner, PsbtSigner, Signer};
+use signing::restore_labitbu_leaf;
+pub use signing::{
+    add_labitbu_signature, finalize_labitbu_input, finalize_labitbu_inputs, finalize_mint,
+    labitbu_sighash, set_mint_sighash, set_sighash_type, sign_labitbu_input, taproot_sighash,
+};
+pub use transfer::{build_transfer, transfer};
+pub use truc::{
+    build_anchor_child, build_truc_mint, mint_anchor_child, mint_truc, TRUC_CHILD_MAX_VSIZE,
+    TRUC_MAX_VSIZE,
+};
+pub use vanity::{TraitFilter, VanityMatch, VanitySearch};
+pub use verify::{verify_finalized, verify_mint, verify_taproot_spends};
+pub use weight::{predicted_vsize, predicted_weight};
+
+/// A funded labitbu deposit: the key and payload that built the deposit
+/// address, plus the UTXO sitting at it.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Deposit {
+    #[serde(deserialize_with = "keys::deserialize_labitbu_pubkey")]
+    pub pubkey: XOnlyPublicKey,
+    pub payload: Vec<u8>,
+    pub outpoint: OutPoint,
+    pub prevout: TxOut,
+}
+
+/// Size every labitbu payload is padded to.
+const PAYLOAD_SIZE: usize = 4096;
+
+/// Names of the base images, in the order `labitbu-traits.json` lists them.
+pub const BASE_NAMES: [&str; 4] = ["normal", "sad", "angry", "sleepy"];
+/// Names of the accessories, in the order `labitbu-traits-sleepy.json` lists
+/// them.
+pub const ACCESSORY_NAMES: [&str; 3] = ["pinkGlasses", "horns", "sleepMask"];
+
+/// Index of the sleepy base image.
+const SLEEPY_BASE: usize = 3;
+/// Index of the sleep mask accessory.
+const SLEEP_MASK: usize = 2;
+
 #[wasm_bindgen]
 pub fn generate_labitbu_bytes(
     pubkey_hex: &str,
     base_images_js: JsValue,
     accessories_js: JsValue,
-) -> Result<Box<[u8]>, JsValue> {
-    const TARGET_SIZE: usize = 4096;
+) -> Result<LabitbuImage, JsValue> {
+    let base_images: Vec<Vec<u8>> = from_value(base_images_js)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse base images: {}", e)))?;
+    let accessories: Vec<Vec<u8>> = from_value(accessories_js)
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse accessories: {}", e)))?;
 
+    let pubkey = pubkey_bytes_from_hex(pubkey_hex)?;
+
+    if base_images.is_empty() {
+        return Err(JsValue::from_str("No base images provided"));
+    }
+    let traits = labitbu_traits(&pubkey, base_images.len(), accessories.len());
+    let payload = render_payload(&base_images, &accessories, &traits)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(LabitbuImage::new(payload, traits))
+}
+
+#[wasm_bindgen]
+pub fn generate_labitbu_bytes_sleepy(
+    pubkey_hex: &str,
+    base_images_js: JsValue,
+    accessories_js: JsValue,
+) -> Result<LabitbuImage, JsValue> {
     let base_images: Vec<Vec<u8>> = from_value(base_images_js)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse base images: {}", e)))?;
     let accessories: Vec<Vec<u8>> = from_value(accessories_js)
         .map_err(|e| JsValue::from_str(&format!("Failed to parse accessories: {}", e)))?;
 
+    let pubkey = pubkey_bytes_from_hex(pubkey_hex)?;
+
     if base_images.is_empty() {
         return Err(JsValue::from_str("No base images provided"));
     }
+    let traits = labitbu_traits_sleepy(&pubkey, accessories.len());
+    let payload = render_payload(&base_images, &accessories, &traits)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
-    let mut rng = create_rng_from_pubkey(pubkey_hex)?;
+    Ok(LabitbuImage::new(payload, traits))
+}
 
-    let base_idx = (rng.next_u32() as usize) % base_images.len();
-    let base_image_data = &base_images[base_idx];
+/// The traits a pubkey rolls: which base image, which accessory if any, and
+/// how far round the colour wheel the body is shifted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct LabitbuTraits {
+    pub base: usize,
+    pub accessory: Option<usize>,
+    pub hue_shift: u32,
+    pub sleepy: bool,
+}
 
-    let mut base_img = image::load_from_memory(base_image_data)
-        .map_err(|e| JsValue::from_str(&format!("Failed to load base image: {}", e)))?
-        .to_rgba8();
+/// Rolls the traits of the labitbu for `pubkey` out of `base_count` base
+/// images and `accessory_count` accessories.
+pub fn labitbu_traits(
+    pubkey: &[u8; 32],
+    base_count: usize,
+    accessory_count: usize,
+) -> LabitbuTraits {
+    let mut rng = rng_from_pubkey(pubkey);
 
-    let accessory_idx = if !accessories.is_empty() {
-        let roll = (rng.next_u32() as usize) % (accessories.len() + 1);
-        if roll < accessories.len() {
+    let base = (rng.next_u32() as usize) % base_count;
+
+    let accessory = if accessory_count > 0 {
+        let roll = (rng.next_u32() as usize) % (accessory_count + 1);
+        if roll < accessory_count {
             Some(roll)
         } else {
             None
@@ -55,76 +218,76 @@ pub fn generate_labitbu_bytes(
         None
     };
 
-    let hue_shift = (rng.next_u32() % 360) as f32;
-
-    apply_hue_shift(&mut base_img, hue_shift);
-
-    if let Some(acc_idx) = accessory_idx {
-        let accessory_data = &accessories[acc_idx];
-        let mut accessory_img = image::load_from_memory(accessory_data)
-            .map_err(|e| JsValue::from_str(&format!("Failed to load accessory: {}", e)))?
-            .to_rgba8();
-
-        accessory_img = imageops::resize(
-            &accessory_img,
-            base_img.width(),
-            base_img.height(),
-            imageops::FilterType::Lanczos3,
-        );
+    let hue_shift = rng.next_u32() % 360;
 
-        composite_images(&mut base_img, &accessory_img);
+    LabitbuTraits {
+        base,
+        accessory,
+        hue_shift,
+        sleepy: false,
     }
-
-    let webp_data = encode_to_webp_deterministic(&base_img)?;
-
-    let mut padded = vec![0u8; TARGET_SIZE];
-    padded[..webp_data.len()].copy_from_slice(&webp_data);
-
-    Ok(padded.into_boxed_slice())
 }
 
-#[wasm_bindgen]
-pub fn generate_labitbu_bytes_sleepy(
-    pubkey_hex: &str,
-    base_images_js: JsValue,
-    accessories_js: JsValue,
-) -> Result<Box<[u8]>, JsValue> {
-    const TARGET_SIZE: usize = 4096;
+/// The traits of the sleepy variant: always the sleepy body with a sleep
+/// mask, only the hue comes from the key.
+pub fn labitbu_traits_sleepy(pubkey: &[u8; 32], accessory_count: usize) -> LabitbuTraits {
+    let mut rng = rng_from_pubkey(pubkey);
 
-    let base_images: Vec<Vec<u8>> = from_value(base_images_js)
-        .map_err(|e| JsValue::from_str(&format!("Failed to parse base images: {}", e)))?;
-    let accessories: Vec<Vec<u8>> = from_value(accessories_js)
-        .map_err(|e| JsValue::from_str(&format!("Failed to parse accessories: {}", e)))?;
+    LabitbuTraits {
+        base: SLEEPY_BASE,
+        accessory: (accessory_count > 0).then_some(SLEEP_MASK),
+        hue_shift: rng.next_u32() % 360,
+        sleepy: true,
+    }
+}
 
+/// Renders the labitbu for `pubkey` and pads it to the 4096-byte payload
+/// committed to in its deposit address.
+pub fn labitbu_payload(
+    pubkey: &[u8; 32],
+    base_images: &[Vec<u8>],
+    accessories: &[Vec<u8>],
+) -> Result<Vec<u8>, Error> {
     if base_images.is_empty() {
-        return Err(JsValue::from_str("No base images provided"));
+        return Err(Error::Image("No base images provided".to_string()));
     }
+    let traits = labitbu_traits(pubkey, base_images.len(), accessories.len());
+    render_payload(base_images, accessories, &traits)
+}
 
-    let mut rng = create_rng_from_pubkey(pubkey_hex)?;
-
-    // Only use sleepy labitbu (index 3 in the array)
-    let base_idx = 3; // Force sleepy labitbu
-    let base_image_data = &base_images[base_idx];
+/// Renders the sleepy variant of the labitbu for `pubkey`.
+pub fn labitbu_payload_sleepy(
+    pubkey: &[u8; 32],
+    base_images: &[Vec<u8>],
+    accessories: &[Vec<u8>],
+) -> Result<Vec<u8>, Error> {
+    if base_images.is_empty() {
+        return Err(Error::Image("No base images provided".to_string()));
+    }
+    let traits = labitbu_traits_sleepy(pubkey, accessories.len());
+    render_payload(base_images, accessories, &traits)
+}
 
+fn render_payload(
+    base_images: &[Vec<u8>],
+    accessories: &[Vec<u8>],
+    traits: &LabitbuTraits,
+) -> Result<Vec<u8>, Error> {
+    let base_image_data = base_images
+        .get(traits.base)
+        .ok_or_else(|| Error::Image(format!("No base image {}", traits.base)))?;
     let mut base_img = image::load_from_memory(base_image_data)
-        .map_err(|e| JsValue::from_str(&format!("Failed to load base image: {}", e)))?
+        .map_err(|e| Error::Image(format!("Failed to load base image: {}", e)))?
         .to_rgba8();
 
-    // Always add sleep mask (index 2 in accessories array)
-    let accessory_idx = if !accessories.is_empty() {
-        Some(2) // Force sleep mask
-    } else {
-        None
-    };
-
-    let hue_shift = (rng.next_u32() % 360) as f32;
+    apply_hue_shift(&mut base_img, traits.hue_shift as f32);
 
-    apply_hue_shift(&mut base_img, hue_shift);
-
-    if let Some(acc_idx) = accessory_idx {
-        let accessory_data = &accessories[acc_idx];
+    if let Some(acc_idx) = traits.accessory {
+        let accessory_data = accessories
+            .get(acc_idx)
+            .ok_or_else(|| Error::Image(format!("No accessory {}", acc_idx)))?;
         let mut accessory_img = image::load_from_memory(accessory_data)
-            .map_err(|e| JsValue::from_str(&format!("Failed to load accessory: {}", e)))?
+            .map_err(|e| Error::Image(format!("Failed to load accessory: {}", e)))?
             .to_rgba8();
 
         accessory_img = imageops::resize(
@@ -138,25 +301,35 @@ pub fn generate_labitbu_bytes_sleepy(
     }
 
     let webp_data = encode_to_webp_deterministic(&base_img)?;
+    if webp_data.len() > PAYLOAD_SIZE {
+        return Err(Error::Image(format!(
+            "Encoded image is {} bytes, over the {} byte cap",
+            webp_data.len(),
+            PAYLOAD_SIZE
+        )));
+    }
 
-    let mut padded = vec![0u8; TARGET_SIZE];
+    let mut padded = vec![0u8; PAYLOAD_SIZE];
     padded[..webp_data.len()].copy_from_slice(&webp_data);
 
-    Ok(padded.into_boxed_slice())
+    Ok(padded)
 }
 
-fn create_rng_from_pubkey(pubkey_hex: &str) -> Result<SmallRng, JsValue> {
-    let pubkey_bytes = <[u8; 32]>::from_hex(pubkey_hex)
-        .map_err(|e| JsValue::from_str(&format!("Invalid hex: {}", e)))?;
+/// The bytes generation is seeded with: the same normalized key that goes
+/// into [`spend_script`], so the art always matches the key that can spend.
+fn pubkey_bytes_from_hex(pubkey_hex: &str) -> Result<[u8; 32], JsValue> {
+    parse_labitbu_key(pubkey_hex)
+        .map(|key| key.pubkey.serialize())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
 
+fn rng_from_pubkey(pubkey_bytes: &[u8; 32]) -> SmallRng {
     let mut engine = sha256::Hash::engine();
-    engine.input(&pubkey_bytes);
+    engine.input(pubkey_bytes);
     let hash = sha256::Hash::from_engine(engine);
     let seed_bytes: [u8; 32] = hash.to_byte_array();
 
-    Ok(SmallRng::seed_from_u64(u64::from_le_bytes(
-        seed_bytes[..8].try_into().unwrap(),
-    )))
+    SmallRng::seed_from_u64(u64::from_le_bytes(seed_bytes[..8].try_into().unwrap()))
 }
 
 fn apply_hue_shift(img: &mut RgbaImage, hue_shift: f32) {
@@ -207,7 +380,7 @@ fn composite_images(base: &mut RgbaImage, accessory: &RgbaImage) {
     }
 }
 
-pub fn encode_to_webp_deterministic(img: &RgbaImage) -> Result<Vec<u8>, JsValue> {
+pub fn encode_to_webp_deterministic(img: &RgbaImage) -> Result<Vec<u8>, Error> {
     let (w, h) = img.dimensions();
     let mut out = Vec::new();
 
@@ -222,10 +395,10 @@ pub fn encode_to_webp_deterministic(img: &RgbaImage) -> Result<Vec<u8>, JsValue>
             rgb_buf.extend_from_slice(&px.0[..3]);
         }
         enc.encode(&rgb_buf, w, h, ColorType::Rgb8)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            .map_err(|e| Error::Image(e.to_string()))?;
     } else {
         enc.encode(img.as_raw(), w, h, ColorType::Rgba8)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            .map_err(|e| Error::Image(e.to_string()))?;
     }
 
     Ok(out)
@@ -285,6 +458,7 @@ fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (f32, f32, f32) {
 }
 
 #[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
 pub fn mint(
     pubkey_hex: &str,
     payload_bytes: Vec<u8>,
@@ -293,30 +467,26 @@ pub fn mint(
     fee: u64,
     inputs: JsValue,
     prev_txouts: JsValue,
-) -> Result<Box<[u8]>, JsValue> {
-    let pubkey =
-        XOnlyPublicKey::from_str(pubkey_hex).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    signer: Option<JsSigner>,
+) -> Result<PsbtResult, JsValue> {
+    let key = parse_labitbu_key(pubkey_hex).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let pubkey = key.pubkey;
 
     let inputs: Vec<TxIn> = serde_wasm_bindgen::from_value(inputs)
         .map_err(|e| JsValue::from_str(&format!("inputs: {}", e)))?;
     let prev_txouts: Vec<TxOut> = serde_wasm_bindgen::from_value(prev_txouts)
         .map_err(|e| JsValue::from_str(&format!("prev_txouts: {}", e)))?;
-    XOnlyPublicKey::from_str(pubkey_hex).map_err(|e| JsValue::from_str(&e.to_string()))?;
 
     let taproot_spend_info = create_taproot_spend_info(pubkey, payload_bytes)
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
-    let destination_address =
-        Address::from_str(&destination_address).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let destination_address = parse_mainnet_address(&destination_address)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
-    let mut tx_outs = Vec::new();
-    tx_outs.push(TxOut {
+    let tx_outs = vec![TxOut {
         value: Amount::from_sat(amount - fee),
-        script_pubkey: destination_address
-            .require_network(Network::Bitcoin)
-            .map_err(|e| JsValue::from_str(&e.to_string()))?
-            .script_pubkey(),
-    });
+        script_pubkey: destination_address.script_pubkey(),
+    }];
 
     let unsigned_tx: Transaction = Transaction {
         version: bitcoin::transaction::Version(2),
@@ -328,40 +498,66 @@ pub fn mint(
     let mut psbt =
         Psbt::from_unsigned_tx(unsigned_tx).map_err(|e| JsValue::from_str(&e.to_string()))?;
 
+    for (psbt_in, prev_txout) in psbt.inputs.iter_mut().zip(prev_txouts) {
+        psbt_in.witness_utxo = Some(prev_txout);
+        add_labitbu_leaf(psbt_in, pubkey, &taproot_spend_info);
+        add_key_origin(psbt_in, &key);
+    }
+
+    assert_sat_preserved(&psbt, 0, 0).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    sign_built_psbt(&mut psbt, signer.as_ref()).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+/// Parses an address and checks that it is for mainnet, as every labitbu is.
+///
+/// Silent payment addresses are recognized so they can be refused with a
+/// clear error. Their output is derived from the private keys of all the
+/// inputs, and BIP352 counts a labitbu deposit by its output key, whose
+/// internal key is a NUMS point no one has the secret for.
+fn parse_mainnet_address(address: &str) -> Result<Address, Error> {
+    let lower = address.trim().to_ascii_lowercase();
+    if lower.starts_with("sp1") || lower.starts_with("tsp1") {
+        return Err(Error::SilentPayment);
+    }
+    Ok(Address::from_str(address)?.require_network(Network::Bitcoin)?)
+}
+
+/// Fills in the script-path spend fields for an input paying to a labitbu
+/// deposit address.
+fn add_labitbu_leaf(
+    psbt_in: &mut psbt::Input,
+    pubkey: XOnlyPublicKey,
+    taproot_spend_info: &TaprootSpendInfo,
+) {
     let spend_script = spend_script(pubkey);
-    let labitbu_nums = nums_from_tag(b"Labitbu");
     let ctrl_block = taproot_spend_info
         .control_block(&(spend_script.clone(), LeafVersion::TapScript))
         .expect("control block must exist");
-    let sighash_ty = TapSighashType::Default.into();
 
-    for (psbt_in, prev_txout) in psbt.inputs.iter_mut().zip(prev_txouts.into_iter()) {
-        psbt_in.witness_utxo = Some(prev_txout);
-        psbt_in.tap_internal_key = Some(labitbu_nums);
-        psbt_in.tap_scripts.insert(
-            ctrl_block.clone(),
-            (spend_script.clone(), LeafVersion::TapScript),
-        );
-        psbt_in.sighash_type = Some(sighash_ty);
-    }
-
-    Ok(psbt.serialize().into_boxed_slice())
+    psbt_in.tap_internal_key = Some(nums_from_tag(b"Labitbu"));
+    psbt_in
+        .tap_scripts
+        .insert(ctrl_block, (spend_script, LeafVersion::TapScript));
+    psbt_in.sighash_type = Some(TapSighashType::Default.into());
 }
 
 #[wasm_bindgen]
 pub fn create_deposit_address(
     pubkey_hex: &str,
     payload_bytes: Vec<u8>,
-) -> Result<Box<[u8]>, JsValue> {
-    let pubkey =
-        XOnlyPublicKey::from_str(pubkey_hex).map_err(|e| JsValue::from_str(&e.to_string()))?;
+) -> Result<DepositAddress, JsValue> {
+    let pubkey = parse_labitbu_key(pubkey_hex)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?
+        .pubkey;
 
     let taproot_spend_info = create_taproot_spend_info(pubkey, payload_bytes)
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
     let address = Address::p2tr_tweaked(taproot_spend_info.output_key(), Network::Bitcoin);
 
-    Ok(address.to_string().into_bytes().into_boxed_slice())
+    Ok(address.into())
 }
 
 pub fn create_taproot_spend_info(
@@ -408,7 +604,7 @@ fn nums_from_tag(tag: &[u8]) -> XOnlyPublicKey {
 
 fn build_merkle_path_from_bytes(bytes: &[u8]) -> Vec<TapNodeHash> {
     let mut padded = bytes.to_vec();
-    while padded.len() % 32 != 0 {
+    while !padded.len().is_multiple_of(32) {
         padded.push(0);
     }
 
@@ -435,6 +631,19 @@ mod tests {
         hex::decode(s).expect("Failed to decode hex string")
     }
 
+    #[test]
+    fn silent_payment_destinations_are_refused() {
+        let sp = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+        assert!(matches!(
+            parse_mainnet_address(sp),
+            Err(Error::SilentPayment)
+        ));
+        assert!(parse_mainnet_address(
+            "bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3"
+        )
+        .is_ok());
+    }
+
     #[wasm_bindgen_test]
     fn generated_images_are_always_under_4096_with_real_data() {
         let base_images = vec![
@@ -0,0 +1,355 @@
+use bitcoin::{
+    absolute,
+    psbt::{raw::ProprietaryKey, PsbtSighashType},
+    transaction, Amount, EcdsaSighashType, FeeRate, Psbt, TapSighashType, Transaction, TxOut,
+};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    add_funding_input, assert_sat_preserved, check_dust, deserialize_psbt, first_sat_locations,
+    parse_mainnet_address, settle_change, sign_built_psbt, Error, FundingInput, FundingKind,
+    JsSigner, PsbtResult, SatLocation,
+};
+
+/// Number of buyer inputs placed ahead of the seller's so the labitbu sat
+/// lands at the start of the buyer's output.
+pub const LISTING_PADDING_INPUTS: usize = 2;
+/// Index of the seller's input, and of the payment output it signs for.
+const SELLER_INDEX: usize = LISTING_PADDING_INPUTS;
+/// Index of the output that receives the labitbu.
+const RECEIVE_INDEX: usize = 1;
+
+/// The proprietary input field [`build_listing`] sets on the seller's input,
+/// so a listed labitbu can be told apart from other `SINGLE|ANYONECANPAY`
+/// inputs such as a sponsor's fee input.
+fn listing_key() -> ProprietaryKey {
+    ProprietaryKey {
+        prefix: b"labitbu".to_vec(),
+        subtype: 0x00,
+        key: vec![],
+    }
+}
+
+/// Builds the seller's half of a sale: the labitbu UTXO as the only input and
+/// `price` paid to `payment_address` as the only output.
+///
+/// The input is marked `SINGLE|ANYONECANPAY`, so once signed it commits only
+/// to itself and the payment output and a buyer can build the rest.
+pub fn build_listing(
+    labitbu: FundingInput,
+    price: Amount,
+    payment_address: &str,
+) -> Result<Psbt, Error> {
+    let payment = parse_mainnet_address(payment_address)?;
+    let sighash_type: PsbtSighashType = match labitbu.kind {
+        FundingKind::P2trKeyPath { .. } => TapSighashType::SinglePlusAnyoneCanPay.into(),
+        FundingKind::P2wpkh { .. } | FundingKind::P2shP2wpkh { .. } => {
+            EcdsaSighashType::SinglePlusAnyoneCanPay.into()
+        }
+        FundingKind::PayToAnchor => return Err(Error::InvalidListing("anchor cannot be listed")),
+    };
+
+    let mut psbt = Psbt::from_unsigned_tx(Transaction {
+        version: transaction::Version(2),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![],
+        output: vec![TxOut {
+            value: price,
+            script_pubkey: payment.script_pubkey(),
+        }],
+    })?;
+    add_funding_input(&mut psbt, labitbu)?;
+    psbt.inputs[0].sighash_type = Some(sighash_type);
+    psbt.inputs[0].proprietary.insert(listing_key(), vec![]);
+
+    Ok(psbt)
+}
+
+/// Completes a signed listing into the buyer's purchase transaction.
+///
+/// Inputs are the two `padding` UTXOs, the seller's input, then `funding`.
+/// Outputs are the merged padding, the labitbu to `receive_address`, the
+/// seller's payment and any change. Since output 0 is exactly as large as the
+/// padding, the labitbu sat becomes the first sat of output 1; this is checked
+/// with [`check_listing_sat`] before the PSBT is returned. Padding worth less
+/// than the dust limit of the change script is refused, as is any other dust
+/// output.
+pub fn complete_listing(
+    listing: &Psbt,
+    padding: Vec<FundingInput>,
+    funding: Vec<FundingInput>,
+    receive_address: &str,
+    change_address: &str,
+    fee_rate: FeeRate,
+) -> Result<Psbt, Error> {
+    if listing.unsigned_tx.input.len() != 1 || listing.unsigned_tx.output.len() != 1 {
+        return Err(Error::InvalidListing("expected one input and one output"));
+    }
+    let sighash = listing.inputs[0]
+        .sighash_type
+        .map(|t| t.to_u32())
+        .unwrap_or_default();
+    if sighash != EcdsaSighashType::SinglePlusAnyoneCanPay.to_u32() {
+        return Err(Error::InvalidListing(
+            "seller input is not SINGLE|ANYONECANPAY",
+        ));
+    }
+    if padding.len() != LISTING_PADDING_INPUTS {
+        return Err(Error::InvalidListing("expected two padding inputs"));
+    }
+    let labitbu_value = listing.inputs[0]
+        .witness_utxo
+        .as_ref()
+        .ok_or(Error::MissingUtxo(0))?
+        .value;
+
+    let receive_script = parse_mainnet_address(receive_address)?.script_pubkey();
+    let change_script = parse_mainnet_address(change_address)?.script_pubkey();
+    let padding_value: Amount = padding.iter().map(|p| p.prevout.value).sum();
+
+    let mut psbt = Psbt::from_unsigned_tx(Transaction {
+        version: transaction::Version(2),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![],
+        output: vec![
+            TxOut {
+                value: padding_value,
+                script_pubkey: change_script.clone(),
+            },
+            TxOut {
+                value: labitbu_value,
+                script_pubkey: receive_script.clone(),
+            },
+            listing.unsigned_tx.output[0].clone(),
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: change_script,
+            },
+        ],
+    })?;
+    for p in padding {
+        add_funding_input(&mut psbt, p)?;
+    }
+    psbt.unsigned_tx
+        .input
+        .push(listing.unsigned_tx.input[0].clone());
+    psbt.inputs.push(listing.inputs[0].clone());
+    for f in funding {
+        add_funding_input(&mut psbt, f)?;
+    }
+
+    settle_change(&mut psbt, fee_rate)?;
+    for (vout, output) in psbt.unsigned_tx.output.iter().enumerate() {
+        check_dust(vout, output)?;
+    }
+    check_listing_sat(&psbt, &receive_script)?;
+
+    Ok(psbt)
+}
+
+/// Checks that the first sat of the seller's input ends up as the first sat of
+/// the buyer's receive output.
+pub fn check_listing_sat(psbt: &Psbt, receive_script: &bitcoin::Script) -> Result<(), Error> {
+    let pays_buyer = psbt
+        .unsigned_tx
+        .output
+        .get(RECEIVE_INDEX)
+        .is_some_and(|o| o.script_pubkey == *receive_script);
+    if !pays_buyer {
+        return Err(Error::InvalidListing(
+            "receive output does not pay the buyer",
+        ));
+    }
+
+    assert_sat_preserved(psbt, SELLER_INDEX, RECEIVE_INDEX)
+}
+
+/// Whether `psbt_in` is the seller's half of a listing, as tagged by
+/// [`build_listing`].
+pub(crate) fn is_listed_input(psbt_in: &bitcoin::psbt::Input) -> bool {
+    psbt_in.proprietary.contains_key(&listing_key())
+}
+
+/// Checks that the listed labitbu at input `input` of a purchase assembled
+/// outside [`complete_listing`] still sits behind padding: its first sat
+/// starts an output other than the seller's payment, which is paired with it
+/// by index, rather than going back to the seller or to the fee.
+pub(crate) fn check_listed_input(psbt: &Psbt, input: usize) -> Result<(), Error> {
+    match first_sat_locations(psbt)?.get(input) {
+        Some(SatLocation::Output { vout, offset: 0 }) if *vout != input => Ok(()),
+        _ => Err(Error::SatNotPreserved { input }),
+    }
+}
+
+#[wasm_bindgen]
+pub fn create_listing(
+    labitbu: JsValue,
+    price: u64,
+    payment_address: String,
+    signer: Option<JsSigner>,
+) -> Result<PsbtResult, JsValue> {
+    let labitbu: FundingInput = serde_wasm_bindgen::from_value(labitbu)
+        .map_err(|e| JsValue::from_str(&format!("labitbu: {}", e)))?;
+
+    let mut psbt = build_listing(labitbu, Amount::from_sat(price), &payment_address)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    sign_built_psbt(&mut psbt, signer.as_ref()).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+#[wasm_bindgen]
+pub fn buy_listing(
+    listing_psbt_bytes: Vec<u8>,
+    padding: JsValue,
+    funding: JsValue,
+    receive_address: String,
+    change_address: String,
+    fee_rate_sat_vb: u64,
+    signer: Option<JsSigner>,
+) -> Result<PsbtResult, JsValue> {
+    let listing =
+        deserialize_psbt(&listing_psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let padding: Vec<FundingInput> = serde_wasm_bindgen::from_value(padding)
+        .map_err(|e| JsValue::from_str(&format!("padding: {}", e)))?;
+    let funding: Vec<FundingInput> = serde_wasm_bindgen::from_value(funding)
+        .map_err(|e| JsValue::from_str(&format!("funding: {}", e)))?;
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb)
+        .ok_or_else(|| JsValue::from_str("fee rate overflow"))?;
+
+    let mut psbt = complete_listing(
+        &listing,
+        padding,
+        funding,
+        &receive_address,
+        &change_address,
+        fee_rate,
+    )
+    .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    sign_built_psbt(&mut psbt, signer.as_ref()).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{hashes::Hash, Address, Network, OutPoint, ScriptBuf, Txid, XOnlyPublicKey};
+
+    use crate::{nums_from_tag, predicted_vsize};
+
+    fn address(key: XOnlyPublicKey) -> String {
+        let secp = secp256k1::Secp256k1::verification_only();
+        Address::p2tr(&secp, key, None, Network::Bitcoin).to_string()
+    }
+
+    fn utxo(key: XOnlyPublicKey, vout: u32, value: u64) -> FundingInput {
+        FundingInput {
+            outpoint: OutPoint::new(Txid::all_zeros(), vout),
+            prevout: TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new_p2tr(
+                    &secp256k1::Secp256k1::verification_only(),
+                    key,
+                    None,
+                ),
+            },
+            kind: FundingKind::P2trKeyPath { internal_key: key },
+        }
+    }
+
+    #[test]
+    fn purchase_puts_labitbu_sat_first_in_buyer_output() {
+        let seller = nums_from_tag(b"seller");
+        let buyer = nums_from_tag(b"buyer");
+
+        let listing = build_listing(
+            utxo(seller, 0, 546),
+            Amount::from_sat(100_000),
+            &address(seller),
+        )
+        .unwrap();
+        assert_eq!(
+            listing.inputs[0].sighash_type.unwrap().to_u32(),
+            TapSighashType::SinglePlusAnyoneCanPay as u32
+        );
+
+        let psbt = complete_listing(
+            &listing,
+            vec![utxo(buyer, 1, 600), utxo(buyer, 2, 600)],
+            vec![utxo(buyer, 3, 200_000)],
+            &address(buyer),
+            &address(buyer),
+            FeeRate::from_sat_per_vb_u32(2),
+        )
+        .unwrap();
+
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input[SELLER_INDEX].previous_output.vout, 0);
+        assert_eq!(tx.output[0].value, Amount::from_sat(1_200));
+        assert_eq!(tx.output[1].value, Amount::from_sat(546));
+        assert_eq!(tx.output[SELLER_INDEX], listing.unsigned_tx.output[0]);
+        assert_eq!(
+            psbt.fee().unwrap(),
+            Amount::from_sat(2 * predicted_vsize(&psbt).unwrap())
+        );
+    }
+
+    #[test]
+    fn sat_check_rejects_padding_that_shifts_the_sat() {
+        let seller = nums_from_tag(b"seller");
+        let buyer = nums_from_tag(b"buyer");
+        let listing = build_listing(
+            utxo(seller, 0, 546),
+            Amount::from_sat(1_000),
+            &address(seller),
+        )
+        .unwrap();
+        let mut psbt = complete_listing(
+            &listing,
+            vec![utxo(buyer, 1, 600), utxo(buyer, 2, 600)],
+            vec![utxo(buyer, 3, 200_000)],
+            &address(buyer),
+            &address(buyer),
+            FeeRate::from_sat_per_vb_u32(2),
+        )
+        .unwrap();
+
+        psbt.unsigned_tx.output[0].value = Amount::from_sat(1_100);
+
+        let receive = psbt.unsigned_tx.output[1].script_pubkey.clone();
+        assert!(check_listing_sat(&psbt, &receive).is_err());
+    }
+
+    #[test]
+    fn purchase_refuses_dust_padding_and_payment() {
+        let seller = nums_from_tag(b"seller");
+        let buyer = nums_from_tag(b"buyer");
+        let purchase = |price, padding| {
+            let listing = build_listing(
+                utxo(seller, 0, 546),
+                Amount::from_sat(price),
+                &address(seller),
+            )
+            .unwrap();
+            complete_listing(
+                &listing,
+                vec![utxo(buyer, 1, padding), utxo(buyer, 2, padding)],
+                vec![utxo(buyer, 3, 200_000)],
+                &address(buyer),
+                &address(buyer),
+                FeeRate::from_sat_per_vb_u32(2),
+            )
+        };
+
+        assert!(matches!(
+            purchase(100_000, 150),
+            Err(Error::DustOutput { vout: 0, .. })
+        ));
+        assert!(matches!(
+            purchase(100, 600),
+            Err(Error::DustOutput { vout: 2, .. })
+        ));
+        assert!(purchase(100_000, 165).is_ok());
+    }
+}
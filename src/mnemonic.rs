@@ -0,0 +1,144 @@
+use std::str::FromStr;
+
+use bip39::Mnemonic;
+use bitcoin::{
+    bip32::{DerivationPath, Xpriv},
+    NetworkKind,
+};
+use rand::RngCore;
+use secp256k1::{Keypair, Secp256k1};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{Error, LabitbuKey};
+
+/// The BIP86 account reserved for labitbu keys, 19522 being "LB" in ASCII.
+///
+/// Labitbu leaf keys are used untweaked, so they must never be keys a wallet
+/// also hands out as BIP86 receive addresses. Wallets scan accounts upwards
+/// from 0 and stop at the first unused one, so an account this far out is
+/// never reached by one.
+pub const LABITBU_ACCOUNT: u32 = 19522;
+
+/// Where labitbu keys live under a mnemonic's master key: the external chain
+/// of the [`LABITBU_ACCOUNT`] BIP86 mainnet account. The key at index `i` is
+/// `m/86'/0'/19522'/0/i`, and is used untweaked as the labitbu leaf key.
+pub const LABITBU_KEY_PATH: &str = "m/86'/0'/19522'/0";
+
+/// Generates a 12 or 24 word English mnemonic.
+pub fn generate_mnemonic(word_count: usize) -> Result<Mnemonic, Error> {
+    let mut entropy = match word_count {
+        12 => vec![0u8; 16],
+        24 => vec![0u8; 32],
+        _ => return Err(Error::InvalidKey("mnemonics must have 12 or 24 words")),
+    };
+    rand::thread_rng().fill_bytes(&mut entropy);
+
+    Ok(Mnemonic::from_entropy(&entropy)?)
+}
+
+/// Derives the labitbu keypair at `index` from `mnemonic` and `passphrase`,
+/// along with its key and origin for PSBTs.
+pub fn mnemonic_keypair(
+    mnemonic: &Mnemonic,
+    passphrase: &str,
+    index: u32,
+) -> Result<(Keypair, LabitbuKey), Error> {
+    let secp = Secp256k1::new();
+    let master = Xpriv::new_master(NetworkKind::Main, &mnemonic.to_seed(passphrase))?;
+    let path = DerivationPath::from_str(&format!("{}/{}", LABITBU_KEY_PATH, index))?;
+    let keypair = master.derive_priv(&secp, &path)?.to_keypair(&secp);
+
+    let key = LabitbuKey {
+        pubkey: keypair.x_only_public_key().0,
+        origin: Some((master.fingerprint(&secp), path)),
+    };
+    Ok((keypair, key))
+}
+
+#[wasm_bindgen]
+pub fn generate_mnemonic_words(word_count: usize) -> Result<String, JsValue> {
+    generate_mnemonic(word_count)
+        .map(|mnemonic| mnemonic.to_string())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        hashes::{sha256, Hash},
+        hex::FromHex,
+        Address, Network,
+    };
+
+    use crate::{create_taproot_spend_info, labitbu_payload, labitbu_traits};
+
+    const ABANDON: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    /// The trait images the site loads, as hex webps keyed by name.
+    const TRAITS: &str = include_str!("../labitbu-traits.json");
+
+    fn trait_image(name: &str) -> Vec<u8> {
+        let key = format!("\"{}\": \"", name);
+        let start = TRAITS.find(&key).unwrap() + key.len();
+        let len = TRAITS[start..].find('"').unwrap();
+        Vec::from_hex(&TRAITS[start..start + len]).unwrap()
+    }
+
+    #[test]
+    fn mnemonic_rebuilds_the_same_labitbu() {
+        let mnemonic = Mnemonic::from_str(ABANDON).unwrap();
+        let (keypair, key) = mnemonic_keypair(&mnemonic, "", 0).unwrap();
+
+        assert_eq!(
+            key.pubkey.to_string(),
+            "575688e97d377f1588da982b8548a1079603b7c6c6eff94bb8b5df0b26e545d0"
+        );
+        assert_eq!(keypair.x_only_public_key().0, key.pubkey);
+        // Not the first BIP86 receiving key, which wallets hand out.
+        assert_ne!(
+            key.pubkey.to_string(),
+            "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115"
+        );
+        let (fingerprint, path) = key.origin.unwrap();
+        assert_eq!(fingerprint.to_string(), "73c5da0a");
+        assert_eq!(path.to_string(), "86'/0'/19522'/0/0");
+
+        let base_images: Vec<_> = ["normal", "sad", "angry", "sleepy"]
+            .into_iter()
+            .map(trait_image)
+            .collect();
+        let accessories: Vec<_> = ["pinkGlasses", "horns"]
+            .into_iter()
+            .map(trait_image)
+            .collect();
+        let payload = labitbu_payload(&key.pubkey.serialize(), &base_images, &accessories).unwrap();
+        let spend_info = create_taproot_spend_info(key.pubkey, payload.clone()).unwrap();
+
+        let traits = labitbu_traits(&key.pubkey.serialize(), 4, 2);
+        assert_eq!(
+            (traits.base, traits.accessory, traits.hue_shift),
+            (2, None, 183)
+        );
+
+        assert_eq!(
+            sha256::Hash::hash(&payload).to_string(),
+            "986212ea9599f24e560bd6a0e73ad10f288e67ddaa2ec8ce6ca3954c0bcd7983"
+        );
+        assert_eq!(
+            Address::p2tr_tweaked(spend_info.output_key(), Network::Bitcoin).to_string(),
+            "bc1pwf7rwmlp5nv7l27m4uke9zyrvk8vgvrq5wx9w3ppwh0hu5m94ydswu0rr5"
+        );
+
+        let (_, with_passphrase) = mnemonic_keypair(&mnemonic, "TREZOR", 0).unwrap();
+        assert_ne!(with_passphrase.pubkey, key.pubkey);
+    }
+
+    #[test]
+    fn generates_only_standard_lengths() {
+        assert_eq!(generate_mnemonic(12).unwrap().word_count(), 12);
+        assert_eq!(generate_mnemonic(24).unwrap().word_count(), 24);
+        assert!(generate_mnemonic(15).is_err());
+        assert!(Mnemonic::from_str("abandon abandon abandon").is_err());
+    }
+}
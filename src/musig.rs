@@ -0,0 +1,764 @@
+use bitcoin::{
+    hashes::{sha256, Hash, HashEngine},
+    Psbt, XOnlyPublicKey,
+};
+use rand::RngCore;
+use secp256k1::{schnorr, Message, PublicKey, Scalar, Secp256k1, SecretKey};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    add_labitbu_signature, deserialize_psbt, labitbu_sighash, EphemeralKey, Error, PsbtResult,
+};
+
+/// The order of the secp256k1 group.
+const CURVE_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+fn tagged_hash(tag: &str, parts: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for part in parts {
+        engine.input(part);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// Reduces a hash mod the curve order. Scalars are kept as [`SecretKey`]s
+/// to reuse its arithmetic, so zero, which only turns up with negligible
+/// probability, is an error.
+fn hash_to_scalar(mut hash: [u8; 32]) -> Result<SecretKey, Error> {
+    if hash >= CURVE_ORDER {
+        let mut borrow = 0;
+        for i in (0..32).rev() {
+            let diff = hash[i] as i16 - CURVE_ORDER[i] as i16 - borrow;
+            borrow = (diff < 0) as i16;
+            hash[i] = diff.rem_euclid(256) as u8;
+        }
+    }
+    SecretKey::from_slice(&hash).map_err(|_| Error::Musig("scalar is zero"))
+}
+
+fn add(a: &SecretKey, b: &SecretKey) -> Result<SecretKey, Error> {
+    a.add_tweak(&Scalar::from(*b))
+        .map_err(|_| Error::Musig("scalar is zero"))
+}
+
+fn mul(a: &SecretKey, b: &SecretKey) -> Result<SecretKey, Error> {
+    a.mul_tweak(&Scalar::from(*b))
+        .map_err(|_| Error::Musig("scalar is zero"))
+}
+
+fn point_mul(point: &PublicKey, scalar: &SecretKey) -> PublicKey {
+    point
+        .mul_tweak(&Secp256k1::verification_only(), &Scalar::from(*scalar))
+        .expect("non-zero scalar times a point is a point")
+}
+
+/// Adds points, where `None` is the point at infinity.
+fn point_sum(points: &[Option<PublicKey>]) -> Option<PublicKey> {
+    let points: Vec<&PublicKey> = points.iter().flatten().collect();
+    if points.is_empty() {
+        return None;
+    }
+    PublicKey::combine_keys(&points).ok()
+}
+
+fn has_even_y(point: &PublicKey) -> bool {
+    point.serialize()[0] == 0x02
+}
+
+/// Sorts keys as BIP327's `KeySort` does, so every participant gets the same
+/// aggregate key whatever order they list the keys in.
+pub fn key_sort(pubkeys: &mut [PublicKey]) {
+    pubkeys.sort_by_key(|pk| pk.serialize());
+}
+
+/// The participants' keys and their MuSig2 (BIP327) aggregate.
+///
+/// A co-owned deposit is an ordinary labitbu deposit whose leaf key is the
+/// aggregate key, so art, deposit address and mint all use it as usual. The
+/// leaf signature then takes two rounds: everyone shares a public nonce,
+/// then a partial signature, and the partial signatures are combined.
+/// Tweaking is not supported, as the leaf uses the aggregate key untweaked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyAggContext {
+    pubkeys: Vec<PublicKey>,
+    coefficients: Vec<SecretKey>,
+    aggregate: PublicKey,
+}
+
+impl KeyAggContext {
+    /// Aggregates `pubkeys` in the order given. Use [`key_sort`] first unless
+    /// the participants agreed on an order.
+    pub fn new(pubkeys: Vec<PublicKey>) -> Result<Self, Error> {
+        if pubkeys.is_empty() {
+            return Err(Error::Musig("no keys to aggregate"));
+        }
+        let serialized: Vec<[u8; 33]> = pubkeys.iter().map(|pk| pk.serialize()).collect();
+        let list_hash = tagged_hash("KeyAgg list", &[&serialized.concat()]);
+        let second_key = serialized.iter().find(|pk| **pk != serialized[0]);
+
+        let one = SecretKey::from_slice(&Scalar::ONE.to_be_bytes()).expect("one is a valid key");
+        let coefficients = serialized
+            .iter()
+            .map(|pk| {
+                if Some(pk) == second_key {
+                    Ok(one)
+                } else {
+                    hash_to_scalar(tagged_hash("KeyAgg coefficient", &[&list_hash, pk]))
+                }
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let terms: Vec<Option<PublicKey>> = pubkeys
+            .iter()
+            .zip(&coefficients)
+            .map(|(pk, a)| Some(point_mul(pk, a)))
+            .collect();
+        let aggregate = point_sum(&terms).ok_or(Error::Musig("aggregate key is infinite"))?;
+
+        Ok(KeyAggContext {
+            pubkeys,
+            coefficients,
+            aggregate,
+        })
+    }
+
+    /// The aggregate key, to use wherever a labitbu takes its pubkey.
+    pub fn aggregate_key(&self) -> XOnlyPublicKey {
+        self.aggregate.x_only_public_key().0
+    }
+
+    pub fn pubkeys(&self) -> &[PublicKey] {
+        &self.pubkeys
+    }
+
+    fn coefficient(&self, pubkey: &PublicKey) -> Result<&SecretKey, Error> {
+        self.pubkeys
+            .iter()
+            .position(|pk| pk == pubkey)
+            .map(|i| &self.coefficients[i])
+            .ok_or(Error::Musig("key is not a participant"))
+    }
+}
+
+/// A participant's secret nonce. It is consumed by signing, so it cannot be
+/// used twice: signing two messages with one nonce reveals the secret key.
+pub struct SecNonce([u8; 97]);
+
+/// A participant's public nonce, shared in the first round.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PubNonce([u8; 66]);
+
+/// The sum of every participant's public nonce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AggNonce([u8; 66]);
+
+/// A participant's partial signature, shared in the second round.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PartialSig(SecretKey);
+
+fn parse_point(bytes: &[u8]) -> Result<PublicKey, Error> {
+    PublicKey::from_slice(bytes).map_err(|_| Error::Musig("invalid nonce point"))
+}
+
+/// Parses a point that may be the point at infinity, encoded as zeros.
+fn parse_point_ext(bytes: &[u8]) -> Result<Option<PublicKey>, Error> {
+    if bytes.iter().all(|b| *b == 0) {
+        Ok(None)
+    } else {
+        parse_point(bytes).map(Some)
+    }
+}
+
+impl PubNonce {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        let bytes: [u8; 66] = bytes
+            .try_into()
+            .map_err(|_| Error::Musig("public nonce must be 66 bytes"))?;
+        parse_point(&bytes[..33])?;
+        parse_point(&bytes[33..])?;
+        Ok(PubNonce(bytes))
+    }
+
+    pub fn serialize(&self) -> [u8; 66] {
+        self.0
+    }
+
+    fn points(&self) -> (PublicKey, PublicKey) {
+        (
+            parse_point(&self.0[..33]).expect("checked on creation"),
+            parse_point(&self.0[33..]).expect("checked on creation"),
+        )
+    }
+}
+
+impl AggNonce {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        let bytes: [u8; 66] = bytes
+            .try_into()
+            .map_err(|_| Error::Musig("aggregate nonce must be 66 bytes"))?;
+        parse_point_ext(&bytes[..33])?;
+        parse_point_ext(&bytes[33..])?;
+        Ok(AggNonce(bytes))
+    }
+
+    pub fn serialize(&self) -> [u8; 66] {
+        self.0
+    }
+}
+
+impl PartialSig {
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        SecretKey::from_slice(bytes)
+            .map(PartialSig)
+            .map_err(|_| Error::Musig("invalid partial signature"))
+    }
+
+    pub fn serialize(&self) -> [u8; 32] {
+        self.0.secret_bytes()
+    }
+}
+
+fn nonce_gen_internal(
+    rand: [u8; 32],
+    secret_key: Option<&SecretKey>,
+    pubkey: &PublicKey,
+    aggregate_key: Option<&XOnlyPublicKey>,
+    msg: Option<&[u8]>,
+    extra_in: Option<&[u8]>,
+) -> Result<(SecNonce, PubNonce), Error> {
+    let mut rand = rand;
+    if let Some(secret_key) = secret_key {
+        let aux = tagged_hash("MuSig/aux", &[&rand]);
+        for (r, (s, a)) in rand
+            .iter_mut()
+            .zip(secret_key.secret_bytes().iter().zip(aux))
+        {
+            *r = s ^ a;
+        }
+    }
+    let aggregate_key = aggregate_key
+        .map(|k| k.serialize().to_vec())
+        .unwrap_or_default();
+    let msg_prefixed = match msg {
+        Some(msg) => [&[1][..], &(msg.len() as u64).to_be_bytes(), msg].concat(),
+        None => vec![0],
+    };
+    let extra_in = extra_in.unwrap_or_default();
+
+    let secp = Secp256k1::signing_only();
+    let mut secnonce = [0u8; 97];
+    let mut pubnonce = [0u8; 66];
+    for i in 0..2u8 {
+        let k = hash_to_scalar(tagged_hash(
+            "MuSig/nonce",
+            &[
+                &rand,
+                &[33],
+                &pubkey.serialize(),
+                &[aggregate_key.len() as u8],
+                &aggregate_key,
+                &msg_prefixed,
+                &(extra_in.len() as u32).to_be_bytes(),
+                extra_in,
+                &[i],
+            ],
+        ))?;
+        let i = i as usize;
+        secnonce[32 * i..32 * (i + 1)].copy_from_slice(&k.secret_bytes());
+        pubnonce[33 * i..33 * (i + 1)]
+            .copy_from_slice(&PublicKey::from_secret_key(&secp, &k).serialize());
+    }
+    secnonce[64..].copy_from_slice(&pubkey.serialize());
+
+    Ok((SecNonce(secnonce), PubNonce(pubnonce)))
+}
+
+/// Generates a nonce pair for `pubkey` to sign `msg` under `aggregate_key`.
+///
+/// Only fresh randomness is required; the secret key, aggregate key and
+/// message are mixed in as extra protection against a weak random source.
+pub fn nonce_gen(
+    secret_key: Option<&SecretKey>,
+    pubkey: &PublicKey,
+    aggregate_key: Option<&XOnlyPublicKey>,
+    msg: Option<&[u8; 32]>,
+) -> Result<(SecNonce, PubNonce), Error> {
+    let mut rand = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut rand);
+    nonce_gen_internal(
+        rand,
+        secret_key,
+        pubkey,
+        aggregate_key,
+        msg.map(|m| &m[..]),
+        None,
+    )
+}
+
+/// Sums the participants' public nonces.
+pub fn nonce_agg(pubnonces: &[PubNonce]) -> AggNonce {
+    let mut aggnonce = [0u8; 66];
+    for j in 0..2 {
+        let points: Vec<Option<PublicKey>> = pubnonces
+            .iter()
+            .map(|nonce| Some(parse_point(&nonce.0[33 * j..33 * (j + 1)]).unwrap()))
+            .collect();
+        if let Some(sum) = point_sum(&points) {
+            aggnonce[33 * j..33 * (j + 1)].copy_from_slice(&sum.serialize());
+        }
+    }
+    AggNonce(aggnonce)
+}
+
+/// The values every participant derives from the aggregate nonce and the
+/// message before signing.
+pub struct SigningSession {
+    key_agg: KeyAggContext,
+    msg: [u8; 32],
+    nonce_coefficient: SecretKey,
+    final_nonce: PublicKey,
+    challenge: SecretKey,
+}
+
+impl SigningSession {
+    pub fn new(key_agg: KeyAggContext, aggnonce: &AggNonce, msg: [u8; 32]) -> Result<Self, Error> {
+        let aggregate_key = key_agg.aggregate_key().serialize();
+        let nonce_coefficient = hash_to_scalar(tagged_hash(
+            "MuSig/noncecoef",
+            &[&aggnonce.0, &aggregate_key, &msg],
+        ))?;
+        let r1 = parse_point_ext(&aggnonce.0[..33])?;
+        let r2 = parse_point_ext(&aggnonce.0[33..])?;
+        let final_nonce = point_sum(&[r1, r2.map(|r2| point_mul(&r2, &nonce_coefficient))])
+            .unwrap_or_else(|| {
+                PublicKey::from_secret_key(
+                    &Secp256k1::signing_only(),
+                    &SecretKey::from_slice(&Scalar::ONE.to_be_bytes()).expect("one is a valid key"),
+                )
+            });
+        let challenge = hash_to_scalar(tagged_hash(
+            "BIP0340/challenge",
+            &[
+                &final_nonce.x_only_public_key().0.serialize(),
+                &aggregate_key,
+                &msg,
+            ],
+        ))?;
+
+        Ok(SigningSession {
+            key_agg,
+            msg,
+            nonce_coefficient,
+            final_nonce,
+            challenge,
+        })
+    }
+
+    /// The signer's key negated if the aggregate key has an odd y, so the
+    /// partial signatures add up under the x-only aggregate key.
+    fn signing_key(&self, secret_key: &SecretKey) -> SecretKey {
+        if has_even_y(&self.key_agg.aggregate) {
+            *secret_key
+        } else {
+            secret_key.negate()
+        }
+    }
+
+    /// Signs with `secret_key`, consuming `secnonce`.
+    pub fn partial_sign(
+        &self,
+        secnonce: SecNonce,
+        secret_key: &SecretKey,
+    ) -> Result<PartialSig, Error> {
+        let secp = Secp256k1::signing_only();
+        let mut k1 = SecretKey::from_slice(&secnonce.0[..32])
+            .map_err(|_| Error::Musig("invalid secret nonce"))?;
+        let mut k2 = SecretKey::from_slice(&secnonce.0[32..64])
+            .map_err(|_| Error::Musig("invalid secret nonce"))?;
+        if !has_even_y(&self.final_nonce) {
+            k1 = k1.negate();
+            k2 = k2.negate();
+        }
+        let pubkey = PublicKey::from_secret_key(&secp, secret_key);
+        if secnonce.0[64..] != pubkey.serialize() {
+            return Err(Error::Musig("secret nonce was made for another key"));
+        }
+        let coefficient = self.key_agg.coefficient(&pubkey)?;
+        let d = self.signing_key(secret_key);
+
+        let s = add(
+            &add(&k1, &mul(&self.nonce_coefficient, &k2)?)?,
+            &mul(&mul(&self.challenge, coefficient)?, &d)?,
+        )?;
+        Ok(PartialSig(s))
+    }
+
+    /// Checks one participant's partial signature against their public
+    /// nonce, so a bad share can be blamed on who sent it.
+    pub fn verify_partial(
+        &self,
+        partial: &PartialSig,
+        pubnonce: &PubNonce,
+        pubkey: &PublicKey,
+    ) -> Result<(), Error> {
+        let secp = Secp256k1::new();
+        let (r1, r2) = pubnonce.points();
+        let mut nonce = point_sum(&[Some(r1), Some(point_mul(&r2, &self.nonce_coefficient))]);
+        if !has_even_y(&self.final_nonce) {
+            nonce = nonce.map(|n| n.negate(&secp));
+        }
+        let coefficient = self.key_agg.coefficient(pubkey)?;
+        let mut key_term = point_mul(pubkey, &mul(&self.challenge, coefficient)?);
+        if !has_even_y(&self.key_agg.aggregate) {
+            key_term = key_term.negate(&secp);
+        }
+
+        let expected = point_sum(&[nonce, Some(key_term)]);
+        if expected != Some(PublicKey::from_secret_key(&secp, &partial.0)) {
+            return Err(Error::Musig("partial signature does not verify"));
+        }
+        Ok(())
+    }
+
+    /// Combines every participant's partial signature into a BIP340
+    /// signature under the aggregate key.
+    pub fn aggregate(&self, partials: &[PartialSig]) -> Result<schnorr::Signature, Error> {
+        let (first, rest) = partials
+            .split_first()
+            .ok_or(Error::Musig("no partial signatures"))?;
+        let s = rest
+            .iter()
+            .try_fold(first.0, |sum, partial| add(&sum, &partial.0))?;
+
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.final_nonce.x_only_public_key().0.serialize());
+        bytes[32..].copy_from_slice(&s.secret_bytes());
+        let signature = schnorr::Signature::from_slice(&bytes).expect("64 bytes");
+
+        Secp256k1::verification_only()
+            .verify_schnorr(
+                &signature,
+                &Message::from_digest(self.msg),
+                &self.key_agg.aggregate_key(),
+            )
+            .map_err(|_| Error::Musig("aggregate signature does not verify"))?;
+        Ok(signature)
+    }
+}
+
+/// Verifies the participants' partial signatures on labitbu input `index`
+/// and records their aggregate as the input's signature. `pubnonces` and
+/// `partials` are in the same order as the keys of `key_agg`.
+pub fn combine_labitbu_signature(
+    psbt: &mut Psbt,
+    index: usize,
+    key_agg: KeyAggContext,
+    pubnonces: &[PubNonce],
+    partials: &[PartialSig],
+) -> Result<(), Error> {
+    if pubnonces.len() != key_agg.pubkeys().len() || partials.len() != pubnonces.len() {
+        return Err(Error::Musig("need one nonce and signature per participant"));
+    }
+    let (sighash, _, _) = labitbu_sighash(psbt, index)?;
+    let aggregate_key = key_agg.aggregate_key();
+    let pubkeys = key_agg.pubkeys().to_vec();
+    let session = SigningSession::new(key_agg, &nonce_agg(pubnonces), sighash.to_byte_array())?;
+    for ((partial, pubnonce), pubkey) in partials.iter().zip(pubnonces).zip(&pubkeys) {
+        session.verify_partial(partial, pubnonce, pubkey)?;
+    }
+
+    let signature = session.aggregate(partials)?;
+    add_labitbu_signature(psbt, index, aggregate_key, signature)
+}
+
+fn parse_hex_list<T>(
+    value: JsValue,
+    name: &str,
+    parse: impl Fn(&[u8]) -> Result<T, Error>,
+) -> Result<Vec<T>, JsValue> {
+    let items: Vec<String> = serde_wasm_bindgen::from_value(value)
+        .map_err(|e| JsValue::from_str(&format!("{}: {}", name, e)))?;
+    items
+        .iter()
+        .map(|item| {
+            let bytes =
+                hex::decode(item).map_err(|e| JsValue::from_str(&format!("{}: {}", name, e)))?;
+            parse(&bytes).map_err(|e| JsValue::from_str(&e.to_string()))
+        })
+        .collect()
+}
+
+/// Parses compressed participant keys and sorts them.
+fn sorted_key_agg(pubkeys: JsValue) -> Result<KeyAggContext, JsValue> {
+    let mut pubkeys = parse_hex_list(pubkeys, "pubkeys", |bytes| {
+        PublicKey::from_slice(bytes).map_err(|_| Error::InvalidKey("not a compressed public key"))
+    })?;
+    key_sort(&mut pubkeys);
+
+    KeyAggContext::new(pubkeys).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// The x-only MuSig2 aggregate of the participants' compressed keys, sorted
+/// first. Use it as the pubkey for art, deposit address and mint.
+#[wasm_bindgen]
+pub fn musig_aggregate_key(pubkeys: JsValue) -> Result<String, JsValue> {
+    Ok(sorted_key_agg(pubkeys)?.aggregate_key().to_string())
+}
+
+/// One participant's side of signing a co-owned labitbu input.
+#[wasm_bindgen]
+pub struct MusigSigner {
+    secret_key: SecretKey,
+    key_agg: KeyAggContext,
+    msg: [u8; 32],
+    secnonce: Option<SecNonce>,
+}
+
+#[wasm_bindgen]
+impl MusigSigner {
+    /// Prepares to sign labitbu input `index` of the mint PSBT as `key`, one
+    /// of the participants in `pubkeys`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        key: &EphemeralKey,
+        pubkeys: JsValue,
+        psbt_bytes: Vec<u8>,
+        index: usize,
+    ) -> Result<MusigSigner, JsValue> {
+        let key_agg = sorted_key_agg(pubkeys)?;
+        let psbt = deserialize_psbt(&psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let (sighash, _, _) =
+            labitbu_sighash(&psbt, index).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        key_agg
+            .coefficient(&key.keypair().public_key())
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        Ok(MusigSigner {
+            secret_key: key.keypair().secret_key(),
+            key_agg,
+            msg: sighash.to_byte_array(),
+            secnonce: None,
+        })
+    }
+
+    /// Round one: a fresh public nonce to share, in hex. Calling it again
+    /// replaces the previous nonce.
+    pub fn public_nonce(&mut self) -> Result<String, JsValue> {
+        let (secnonce, pubnonce) = nonce_gen(
+            Some(&self.secret_key),
+            &PublicKey::from_secret_key(&Secp256k1::signing_only(), &self.secret_key),
+            Some(&self.key_agg.aggregate_key()),
+            Some(&self.msg),
+        )
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        self.secnonce = Some(secnonce);
+
+        Ok(hex::encode(pubnonce.serialize()))
+    }
+
+    /// Round two: this participant's partial signature in hex, given every
+    /// participant's public nonce. The nonce is used up, so this can only be
+    /// called once per `public_nonce`.
+    pub fn partial_sign(&mut self, pubnonces: JsValue) -> Result<String, JsValue> {
+        let pubnonces = parse_hex_list(pubnonces, "pubnonces", PubNonce::from_slice)?;
+        let secnonce = self
+            .secnonce
+            .take()
+            .ok_or_else(|| JsValue::from_str("musig: no unused nonce"))?;
+
+        let session = SigningSession::new(self.key_agg.clone(), &nonce_agg(&pubnonces), self.msg)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        let partial = session
+            .partial_sign(secnonce, &self.secret_key)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+        Ok(hex::encode(partial.serialize()))
+    }
+}
+
+/// Combines the participants' nonces and partial signatures into the
+/// signature for labitbu input `index`. Both lists follow the order of
+/// `pubkeys`, as given.
+#[wasm_bindgen]
+pub fn musig_combine(
+    pubkeys: JsValue,
+    psbt_bytes: Vec<u8>,
+    index: usize,
+    pubnonces: JsValue,
+    partial_sigs: JsValue,
+) -> Result<PsbtResult, JsValue> {
+    let given = parse_hex_list(pubkeys, "pubkeys", |bytes| {
+        PublicKey::from_slice(bytes).map_err(|_| Error::InvalidKey("not a compressed public key"))
+    })?;
+    let pubnonces = parse_hex_list(pubnonces, "pubnonces", PubNonce::from_slice)?;
+    let partials = parse_hex_list(partial_sigs, "partial_sigs", PartialSig::from_slice)?;
+    let mut psbt = deserialize_psbt(&psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let mut order: Vec<usize> = (0..given.len()).collect();
+    order.sort_by_key(|&i| given[i].serialize());
+    let sorted_nonces: Vec<PubNonce> = order
+        .iter()
+        .filter_map(|i| pubnonces.get(*i).copied())
+        .collect();
+    let sorted_partials: Vec<PartialSig> = order
+        .iter()
+        .filter_map(|i| partials.get(*i).copied())
+        .collect();
+    let key_agg = KeyAggContext::new(order.iter().map(|i| given[*i]).collect())
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    combine_labitbu_signature(&mut psbt, index, key_agg, &sorted_nonces, &sorted_partials)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn key(hex: &str) -> PublicKey {
+        PublicKey::from_str(hex).unwrap()
+    }
+
+    #[test]
+    fn matches_bip327_vectors() {
+        let keys = [
+            key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            key("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+            key("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66"),
+        ];
+        for (indices, expected) in [
+            (
+                vec![0, 1, 2],
+                "90539EEDE565F5D054F32CC0C220126889ED1E5D193BAF15AEF344FE59D4610C",
+            ),
+            (
+                vec![2, 1, 0],
+                "6204DE8B083426DC6EAF9502D27024D53FC826BF7D2012148A0575435DF54B2B",
+            ),
+            (
+                vec![0, 0, 0],
+                "B436E3BAD62B8CD409969A224731C193D051162D8C5AE8B109306127DA3AA935",
+            ),
+            (
+                vec![0, 0, 1, 1],
+                "69BC22BFA5D106306E48A20679DE1D7389386124D07571D0D872686028C26A3E",
+            ),
+        ] {
+            let ctx = KeyAggContext::new(indices.iter().map(|i| keys[*i]).collect()).unwrap();
+            assert_eq!(ctx.aggregate_key().to_string(), expected.to_lowercase());
+        }
+
+        let secret_key =
+            SecretKey::from_str("7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671")
+                .unwrap();
+        let key_agg = KeyAggContext::new(vec![
+            key("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+            key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            key("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661"),
+        ])
+        .unwrap();
+        let secnonce = SecNonce(hex::decode("508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F703935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9").unwrap().try_into().unwrap());
+        let aggnonce = AggNonce::from_slice(&hex::decode("028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61037496A3CC86926D452CAFCFD55D25972CA1675D549310DE296BFF42F72EEEA8C9").unwrap()).unwrap();
+        let msg = hex::decode("F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF")
+            .unwrap()
+            .try_into()
+            .unwrap();
+
+        let session = SigningSession::new(key_agg, &aggnonce, msg).unwrap();
+        let partial = session.partial_sign(secnonce, &secret_key).unwrap();
+        assert_eq!(
+            hex::encode_upper(partial.serialize()),
+            "012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB"
+        );
+    }
+
+    #[test]
+    fn three_signers_sign_a_co_owned_mint() {
+        use bitcoin::{
+            absolute, transaction, Amount, OutPoint, ScriptBuf, Transaction, TxIn, TxOut, Txid,
+        };
+        use secp256k1::Keypair;
+
+        use crate::{
+            add_labitbu_leaf, create_taproot_spend_info, finalize_labitbu_inputs, verify_finalized,
+        };
+
+        let secp = Secp256k1::new();
+        let keypairs: Vec<Keypair> = (0..3)
+            .map(|_| Keypair::new(&secp, &mut rand::thread_rng()))
+            .collect();
+        let mut pubkeys: Vec<PublicKey> = keypairs.iter().map(|k| k.public_key()).collect();
+        key_sort(&mut pubkeys);
+        let key_agg = KeyAggContext::new(pubkeys.clone()).unwrap();
+        let aggregate_key = key_agg.aggregate_key();
+
+        let spend_info = create_taproot_spend_info(aggregate_key, vec![1u8; 4096]).unwrap();
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+        };
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version(2),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(prevout);
+        add_labitbu_leaf(&mut psbt.inputs[0], aggregate_key, &spend_info);
+        let msg = labitbu_sighash(&psbt, 0).unwrap().0.to_byte_array();
+
+        let signers: Vec<&Keypair> = pubkeys
+            .iter()
+            .map(|pk| keypairs.iter().find(|k| k.public_key() == *pk).unwrap())
+            .collect();
+        let (secnonces, pubnonces): (Vec<SecNonce>, Vec<PubNonce>) = signers
+            .iter()
+            .map(|k| {
+                nonce_gen(
+                    Some(&k.secret_key()),
+                    &k.public_key(),
+                    Some(&aggregate_key),
+                    Some(&msg),
+                )
+                .unwrap()
+            })
+            .unzip();
+        let session = SigningSession::new(key_agg.clone(), &nonce_agg(&pubnonces), msg).unwrap();
+        let partials: Vec<PartialSig> = secnonces
+            .into_iter()
+            .zip(&signers)
+            .map(|(secnonce, k)| session.partial_sign(secnonce, &k.secret_key()).unwrap())
+            .collect();
+
+        let mut tampered = psbt.clone();
+        let swapped = {
+            let mut p = partials.clone();
+            p.swap(0, 1);
+            p
+        };
+        assert!(
+            combine_labitbu_signature(&mut tampered, 0, key_agg.clone(), &pubnonces, &swapped)
+                .is_err()
+        );
+
+        combine_labitbu_signature(&mut psbt, 0, key_agg, &pubnonces, &partials).unwrap();
+        finalize_labitbu_inputs(&mut psbt).unwrap();
+        verify_finalized(&psbt).unwrap();
+    }
+}
@@ -0,0 +1,416 @@
+use bitcoin::{
+    opcodes::{all::OP_CHECKMULTISIG, Class, ClassifyContext},
+    policy::MAX_STANDARD_TX_WEIGHT,
+    script::Instruction,
+    taproot::TAPROOT_CONTROL_MAX_SIZE,
+    Amount, Psbt, PublicKey, Script, Transaction, TxOut, WitnessVersion,
+};
+use serde::Serialize;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    deserialize_psbt, fee_bump::MIN_RELAY_FEE_RATE, weight::with_predicted_witnesses, Error,
+    TRUC_MAX_VSIZE,
+};
+
+/// Smallest non-witness size Bitcoin Core relays, so a transaction cannot be
+/// mistaken for a 64-byte merkle tree node.
+pub const MIN_STANDARD_TX_NONWITNESS_SIZE: usize = 65;
+/// Largest scriptSig Core relays.
+pub const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1_650;
+/// Largest OP_RETURN output script Core relays under its default
+/// `-datacarriersize` before version 30, OP_RETURN opcode included.
+pub const MAX_OP_RETURN_RELAY: usize = 83;
+/// Largest tapscript stack element Core relays, not counting the leaf script
+/// and control block.
+pub const MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE: usize = 80;
+
+/// A reason nodes running default policy would refuse to relay a transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PolicyViolation {
+    /// Only versions 1, 2 and 3 are standard.
+    NonStandardVersion { version: i32 },
+    /// The transaction is over the standard weight limit.
+    TooHeavy { weight: u64, max: u64 },
+    /// The transaction without witnesses is under the minimum size.
+    TooSmall { size: usize, min: usize },
+    /// An output pays a script type nodes do not relay.
+    NonStandardOutput { vout: usize },
+    /// An OP_RETURN output carries more data than nodes relay.
+    OpReturnTooLarge {
+        vout: usize,
+        size: usize,
+        max: usize,
+    },
+    /// More than one OP_RETURN output, which Core refuses before version 30.
+    MultipleOpReturns { count: usize },
+    /// A version 3 (TRUC) transaction is over its size limit.
+    TrucTooLarge { vsize: u64, max: u64 },
+    /// An output is worth less than it would cost to spend.
+    Dust {
+        vout: usize,
+        value: u64,
+        threshold: u64,
+    },
+    /// An input's scriptSig is too large.
+    ScriptSigTooLarge { input: usize, size: usize },
+    /// An input's scriptSig does something other than push data.
+    ScriptSigNotPushOnly { input: usize },
+    /// A tapscript stack element is over the standard size.
+    TapscriptStackItemTooLarge {
+        input: usize,
+        item: usize,
+        size: usize,
+    },
+    /// A taproot control block is deeper than 128 nodes.
+    ControlBlockTooLarge { input: usize, size: usize },
+    /// The fee is under the minimum relay fee.
+    BelowMinRelayFee { fee: u64, required: u64 },
+    /// The outputs are worth more than the inputs they spend.
+    NegativeFee { inputs: u64, outputs: u64 },
+}
+
+/// Whether Core relays outputs paying `script`: P2PKH, P2SH, P2WPKH, P2WSH,
+/// any witness program of version 1 or later, P2PK and bare multisig of up
+/// to three keys.
+fn is_standard_output(script: &Script) -> bool {
+    match script.witness_version() {
+        Some(WitnessVersion::V0) => script.is_p2wpkh() || script.is_p2wsh(),
+        Some(_) => true,
+        None => {
+            script.is_p2pkh() || script.is_p2sh() || script.is_p2pk() || is_bare_multisig(script)
+        }
+    }
+}
+
+/// `OP_m <pubkey>... OP_n OP_CHECKMULTISIG` with `1 <= m <= n <= 3`.
+fn is_bare_multisig(script: &Script) -> bool {
+    let Ok(instructions) = script.instructions().collect::<Result<Vec<_>, _>>() else {
+        return false;
+    };
+    let small_int = |i: &Instruction| match i {
+        Instruction::Op(op) => match op.classify(ClassifyContext::Legacy) {
+            Class::PushNum(n) => Some(n),
+            _ => None,
+        },
+        _ => None,
+    };
+    let [first, keys @ .., n, Instruction::Op(OP_CHECKMULTISIG)] = instructions.as_slice() else {
+        return false;
+    };
+    let (Some(m), Some(n)) = (small_int(first), small_int(n)) else {
+        return false;
+    };
+    let keys_ok = keys.iter().all(|key| {
+        matches!(key, Instruction::PushBytes(bytes) if PublicKey::from_slice(bytes.as_bytes()).is_ok())
+    });
+    keys_ok && 1 <= m && m <= n && n <= 3 && keys.len() == n as usize
+}
+
+/// Lists every default relay policy rule `tx` breaks.
+///
+/// `prevouts` are needed to check the fee and the tapscript witness rules;
+/// without them those checks are skipped. A single dust output is allowed in
+/// a zero-fee transaction, as ephemeral dust, since such a transaction only
+/// relays in a package with the child that spends it.
+pub fn check_policy(tx: &Transaction, prevouts: Option<&[TxOut]>) -> Vec<PolicyViolation> {
+    let mut violations = Vec::new();
+
+    if !(1..=3).contains(&tx.version.0) {
+        violations.push(PolicyViolation::NonStandardVersion {
+            version: tx.version.0,
+        });
+    }
+
+    let weight = tx.weight().to_wu();
+    if weight > u64::from(MAX_STANDARD_TX_WEIGHT) {
+        violations.push(PolicyViolation::TooHeavy {
+            weight,
+            max: u64::from(MAX_STANDARD_TX_WEIGHT),
+        });
+    }
+
+    let vsize = tx.vsize() as u64;
+    if tx.version.0 == 3 && vsize > TRUC_MAX_VSIZE {
+        violations.push(PolicyViolation::TrucTooLarge {
+            vsize,
+            max: TRUC_MAX_VSIZE,
+        });
+    }
+
+    let size = tx.base_size();
+    if size < MIN_STANDARD_TX_NONWITNESS_SIZE {
+        violations.push(PolicyViolation::TooSmall {
+            size,
+            min: MIN_STANDARD_TX_NONWITNESS_SIZE,
+        });
+    }
+
+    let spent: Amount = tx.output.iter().map(|o| o.value).sum();
+    let available: Option<Amount> = prevouts.map(|prevouts| prevouts.iter().map(|o| o.value).sum());
+    let fee = available.and_then(|available| available.checked_sub(spent));
+
+    let dust: Vec<PolicyViolation> = tx
+        .output
+        .iter()
+        .enumerate()
+        .filter_map(|(vout, output)| {
+            let threshold = output.script_pubkey.minimal_non_dust();
+            (!output.script_pubkey.is_op_return() && output.value < threshold).then_some(
+                PolicyViolation::Dust {
+                    vout,
+                    value: output.value.to_sat(),
+                    threshold: threshold.to_sat(),
+                },
+            )
+        })
+        .collect();
+    let ephemeral_dust = dust.len() == 1 && fee == Some(Amount::ZERO);
+    if !ephemeral_dust {
+        violations.extend(dust);
+    }
+
+    for (vout, output) in tx.output.iter().enumerate() {
+        let script = &output.script_pubkey;
+        if script.is_op_return() {
+            if script.len() > MAX_OP_RETURN_RELAY {
+                violations.push(PolicyViolation::OpReturnTooLarge {
+                    vout,
+                    size: script.len(),
+                    max: MAX_OP_RETURN_RELAY,
+                });
+            }
+            if !Script::from_bytes(&script.as_bytes()[1..]).is_push_only() {
+                violations.push(PolicyViolation::NonStandardOutput { vout });
+            }
+        } else if !is_standard_output(script) {
+            violations.push(PolicyViolation::NonStandardOutput { vout });
+        }
+    }
+    let op_returns = tx
+        .output
+        .iter()
+        .filter(|o| o.script_pubkey.is_op_return())
+        .count();
+    if op_returns > 1 {
+        violations.push(PolicyViolation::MultipleOpReturns { count: op_returns });
+    }
+
+    for (input, txin) in tx.input.iter().enumerate() {
+        let size = txin.script_sig.len();
+        if size > MAX_STANDARD_SCRIPTSIG_SIZE {
+            violations.push(PolicyViolation::ScriptSigTooLarge { input, size });
+        }
+        if !txin.script_sig.is_push_only() {
+            violations.push(PolicyViolation::ScriptSigNotPushOnly { input });
+        }
+    }
+
+    if let Some(available) = available.filter(|available| *available < spent) {
+        violations.push(PolicyViolation::NegativeFee {
+            inputs: available.to_sat(),
+            outputs: spent.to_sat(),
+        });
+    }
+
+    if let Some(prevouts) = prevouts {
+        for (input, (txin, prevout)) in tx.input.iter().zip(prevouts).enumerate() {
+            if !prevout.script_pubkey.is_p2tr() {
+                continue;
+            }
+            let Some(control_block) = txin.witness.taproot_control_block() else {
+                continue;
+            };
+            if control_block.len() > TAPROOT_CONTROL_MAX_SIZE {
+                violations.push(PolicyViolation::ControlBlockTooLarge {
+                    input,
+                    size: control_block.len(),
+                });
+            }
+            // Everything before the leaf script is the script's input stack.
+            let annex = usize::from(txin.witness.taproot_annex().is_some());
+            let stack_len = txin.witness.len() - 2 - annex;
+            for (item, element) in txin.witness.iter().take(stack_len).enumerate() {
+                if element.len() > MAX_STANDARD_TAPSCRIPT_STACK_ITEM_SIZE {
+                    violations.push(PolicyViolation::TapscriptStackItemTooLarge {
+                        input,
+                        item,
+                        size: element.len(),
+                    });
+                }
+            }
+        }
+
+        let min_fee = MIN_RELAY_FEE_RATE
+            .fee_vb(tx.vsize() as u64)
+            .unwrap_or(Amount::MAX);
+        if let Some(fee) = fee.filter(|fee| *fee < min_fee && !ephemeral_dust) {
+            violations.push(PolicyViolation::BelowMinRelayFee {
+                fee: fee.to_sat(),
+                required: min_fee.to_sat(),
+            });
+        }
+    }
+
+    violations
+}
+
+/// Checks `psbt` as it will look once signed, filling in placeholder
+/// witnesses for inputs that are not finalized yet.
+pub fn check_psbt_policy(psbt: &Psbt) -> Result<Vec<PolicyViolation>, Error> {
+    let prevouts = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| input.witness_utxo.clone().ok_or(Error::MissingUtxo(i)))
+        .collect::<Result<Vec<TxOut>, Error>>()?;
+    let tx = with_predicted_witnesses(psbt)?;
+
+    Ok(check_policy(&tx, Some(&prevouts)))
+}
+
+#[wasm_bindgen(unchecked_return_type = "PolicyViolation[]")]
+pub fn check_mint_policy(psbt_bytes: Vec<u8>) -> Result<JsValue, JsValue> {
+    let psbt = deserialize_psbt(&psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let violations = check_psbt_policy(&psbt).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    serde_wasm_bindgen::to_value(&violations).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{hashes::Hash, script::PushBytes, OutPoint, ScriptBuf, Txid};
+
+    use crate::{build_truc_mint, create_taproot_spend_info, nums_from_tag, Deposit};
+
+    fn deposit(value: u64) -> Deposit {
+        let pubkey = nums_from_tag(b"depositor");
+        let payload = vec![3u8; 4096];
+        let spend_info = create_taproot_spend_info(pubkey, payload.clone()).unwrap();
+        Deposit {
+            pubkey,
+            payload,
+            outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            prevout: TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+            },
+        }
+    }
+
+    fn destination() -> String {
+        let secp = secp256k1::Secp256k1::verification_only();
+        bitcoin::Address::p2tr(
+            &secp,
+            nums_from_tag(b"dest"),
+            None,
+            bitcoin::Network::Bitcoin,
+        )
+        .to_string()
+    }
+
+    #[test]
+    fn truc_mint_with_ephemeral_anchor_is_standard() {
+        let psbt = build_truc_mint(vec![deposit(10_000)], &destination()).unwrap();
+
+        assert_eq!(check_psbt_policy(&psbt).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn dust_and_low_fee_are_reported() {
+        let mut psbt = build_truc_mint(vec![deposit(10_000)], &destination()).unwrap();
+        psbt.unsigned_tx.output[0].value = Amount::from_sat(9_990);
+        psbt.unsigned_tx.output.push(TxOut {
+            value: Amount::from_sat(1),
+            script_pubkey: psbt.unsigned_tx.output[0].script_pubkey.clone(),
+        });
+        psbt.outputs.push(Default::default());
+
+        let violations = check_psbt_policy(&psbt).unwrap();
+
+        assert!(violations.contains(&PolicyViolation::Dust {
+            vout: 2,
+            value: 1,
+            threshold: 330,
+        }));
+        assert!(violations
+            .iter()
+            .any(|v| matches!(v, PolicyViolation::BelowMinRelayFee { fee: 9, .. })));
+    }
+
+    #[test]
+    fn output_types_follow_core() {
+        let key = secp256k1::PublicKey::from_secret_key(
+            &secp256k1::Secp256k1::new(),
+            &secp256k1::SecretKey::from_slice(&[1u8; 32]).unwrap(),
+        );
+        let key = PublicKey::new(key);
+        let multisig = |m: i64, n: usize| {
+            let mut builder = bitcoin::script::Builder::new().push_int(m);
+            for _ in 0..n {
+                builder = builder.push_key(&key);
+            }
+            builder
+                .push_int(n as i64)
+                .push_opcode(OP_CHECKMULTISIG)
+                .into_script()
+        };
+
+        assert!(is_standard_output(&ScriptBuf::new_p2pk(&key)));
+        assert!(is_standard_output(&multisig(1, 3)));
+        assert!(!is_standard_output(&multisig(1, 4)));
+        assert!(!is_standard_output(&multisig(3, 2)));
+        assert!(!is_standard_output(&ScriptBuf::from_bytes(
+            [&[0x00, 24][..], &[7u8; 24]].concat()
+        )));
+        assert!(is_standard_output(&ScriptBuf::new_p2a()));
+
+        let tx = Transaction {
+            version: bitcoin::transaction::Version(3),
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                witness: bitcoin::Witness::from_slice(&[vec![0u8; 41_000]]),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::ZERO,
+                script_pubkey: ScriptBuf::new_op_return(
+                    <&PushBytes>::try_from(&[9u8; 81][..]).unwrap(),
+                ),
+            }],
+        };
+        let violations = check_policy(&tx, None);
+        assert!(violations.contains(&PolicyViolation::OpReturnTooLarge {
+            vout: 0,
+            size: 84,
+            max: MAX_OP_RETURN_RELAY,
+        }));
+        assert!(violations
+            .iter()
+            .any(|v| matches!(v, PolicyViolation::TrucTooLarge { max: 10_000, .. })));
+    }
+
+    #[test]
+    fn overspending_and_extra_op_returns_are_reported() {
+        let mut psbt = build_truc_mint(vec![deposit(10_000)], &destination()).unwrap();
+        psbt.unsigned_tx.output[0].value = Amount::from_sat(10_001);
+        for _ in 0..2 {
+            psbt.unsigned_tx.output.push(TxOut {
+                value: Amount::ZERO,
+                script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+            });
+            psbt.outputs.push(Default::default());
+        }
+
+        let violations = check_psbt_policy(&psbt).unwrap();
+
+        assert!(violations.contains(&PolicyViolation::NegativeFee {
+            inputs: 10_000,
+            outputs: 10_001,
+        }));
+        assert!(violations.contains(&PolicyViolation::MultipleOpReturns { count: 2 }));
+    }
+}
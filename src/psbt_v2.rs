@@ -0,0 +1,768 @@
+use bitcoin::{
+    absolute, consensus::serialize, hashes::Hash, psbt, transaction, Amount, OutPoint, Psbt,
+    ScriptBuf, Sequence, TapSighashType, Transaction, TxIn, TxOut, Txid, Witness,
+};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    first_sat_locations,
+    listing::{check_listed_input, is_listed_input},
+    Error, PsbtResult, SatLocation,
+};
+
+const MAGIC: &[u8; 5] = b"psbt\xff";
+
+const GLOBAL_UNSIGNED_TX: u64 = 0x00;
+const GLOBAL_TX_VERSION: u64 = 0x02;
+const GLOBAL_FALLBACK_LOCKTIME: u64 = 0x03;
+const GLOBAL_INPUT_COUNT: u64 = 0x04;
+const GLOBAL_OUTPUT_COUNT: u64 = 0x05;
+const GLOBAL_TX_MODIFIABLE: u64 = 0x06;
+const GLOBAL_VERSION: u64 = 0xfb;
+
+const IN_PREVIOUS_TXID: u64 = 0x0e;
+const IN_OUTPUT_INDEX: u64 = 0x0f;
+const IN_SEQUENCE: u64 = 0x10;
+const IN_REQUIRED_TIME_LOCKTIME: u64 = 0x11;
+const IN_REQUIRED_HEIGHT_LOCKTIME: u64 = 0x12;
+
+const OUT_AMOUNT: u64 = 0x03;
+const OUT_SCRIPT: u64 = 0x04;
+
+const INPUTS_MODIFIABLE: u8 = 1 << 0;
+const OUTPUTS_MODIFIABLE: u8 = 1 << 1;
+const HAS_SIGHASH_SINGLE: u8 = 1 << 2;
+
+/// The key-value pairs of one PSBT map, keys including their type.
+type Map = Vec<(Vec<u8>, Vec<u8>)>;
+
+fn read_compact_size(bytes: &[u8], pos: &mut usize) -> Result<u64, Error> {
+    let first = *bytes.get(*pos).ok_or(Error::PsbtV2("truncated"))?;
+    *pos += 1;
+    let width = match first {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        n => return Ok(n as u64),
+    };
+    let raw = bytes
+        .get(*pos..*pos + width)
+        .ok_or(Error::PsbtV2("truncated"))?;
+    *pos += width;
+    let mut le = [0u8; 8];
+    le[..width].copy_from_slice(raw);
+    Ok(u64::from_le_bytes(le))
+}
+
+fn write_compact_size(out: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xfc => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+fn read_slice<'a>(bytes: &'a [u8], pos: &mut usize) -> Result<&'a [u8], Error> {
+    let len = read_compact_size(bytes, pos)? as usize;
+    let slice = bytes
+        .get(*pos..pos.saturating_add(len))
+        .ok_or(Error::PsbtV2("truncated"))?;
+    *pos += len;
+    Ok(slice)
+}
+
+fn read_map(bytes: &[u8], pos: &mut usize) -> Result<Map, Error> {
+    let mut map = Map::new();
+    loop {
+        let key = read_slice(bytes, pos)?;
+        if key.is_empty() {
+            return Ok(map);
+        }
+        let value = read_slice(bytes, pos)?;
+        map.push((key.to_vec(), value.to_vec()));
+    }
+}
+
+fn write_map(out: &mut Vec<u8>, mut map: Map) {
+    map.sort();
+    for (key, value) in map {
+        write_compact_size(out, key.len() as u64);
+        out.extend_from_slice(&key);
+        write_compact_size(out, value.len() as u64);
+        out.extend_from_slice(&value);
+    }
+    out.push(0x00);
+}
+
+fn key_type(key: &[u8]) -> u64 {
+    read_compact_size(key, &mut 0).unwrap_or(u64::MAX)
+}
+
+fn key(key_type: u64) -> Vec<u8> {
+    let mut key = Vec::new();
+    write_compact_size(&mut key, key_type);
+    key
+}
+
+/// Removes the keydata-less field `key_type` from `map`, refusing it if it
+/// has keydata or appears twice.
+fn take(map: &mut Map, key_type_: u64) -> Result<Option<Vec<u8>>, Error> {
+    let mut found = None;
+    let mut i = 0;
+    while i < map.len() {
+        if key_type(&map[i].0) != key_type_ {
+            i += 1;
+            continue;
+        }
+        let (k, value) = map.remove(i);
+        if k != key(key_type_) {
+            return Err(Error::PsbtV2("unexpected keydata"));
+        }
+        if found.replace(value).is_some() {
+            return Err(Error::PsbtV2("duplicate key"));
+        }
+    }
+    Ok(found)
+}
+
+fn take_u32(map: &mut Map, key_type: u64) -> Result<Option<u32>, Error> {
+    take(map, key_type)?
+        .map(|value| {
+            <[u8; 4]>::try_from(value.as_slice())
+                .map(u32::from_le_bytes)
+                .map_err(|_| Error::PsbtV2("field is not 4 bytes"))
+        })
+        .transpose()
+}
+
+fn take_count(map: &mut Map, key_type: u64) -> Result<usize, Error> {
+    let value = take(map, key_type)?.ok_or(Error::PsbtV2("missing input or output count"))?;
+    let mut pos = 0;
+    let count = read_compact_size(&value, &mut pos)?;
+    if pos != value.len() {
+        return Err(Error::PsbtV2("malformed count"));
+    }
+    usize::try_from(count).map_err(|_| Error::PsbtV2("count too large"))
+}
+
+/// The locktime BIP370 picks for the inputs' requirements, or `fallback` if
+/// none of them has one. Heights win when both kinds would do.
+fn determine_locktime(
+    required: &[(Option<u32>, Option<u32>)],
+    fallback: u32,
+) -> Result<absolute::LockTime, Error> {
+    let constrained: Vec<_> = required
+        .iter()
+        .filter(|(time, height)| time.is_some() || height.is_some())
+        .collect();
+    if constrained.is_empty() {
+        return Ok(absolute::LockTime::from_consensus(fallback));
+    }
+    if constrained.iter().all(|(_, height)| height.is_some()) {
+        let height = constrained.iter().filter_map(|(_, h)| *h).max().unwrap();
+        return absolute::LockTime::from_height(height)
+            .map_err(|_| Error::PsbtV2("required height locktime out of range"));
+    }
+    if constrained.iter().all(|(time, _)| time.is_some()) {
+        let time = constrained.iter().filter_map(|(t, _)| *t).max().unwrap();
+        return absolute::LockTime::from_time(time)
+            .map_err(|_| Error::PsbtV2("required time locktime out of range"));
+    }
+    Err(Error::PsbtV2("inputs require incompatible locktimes"))
+}
+
+/// What a signature's sighash type commits to among the outputs.
+enum Outputs {
+    Fixed,
+    Open,
+    Paired,
+}
+
+/// What the signatures on `psbt_in` commit to: whether they are
+/// `ANYONECANPAY`, and which outputs they cover. `None` if it is unsigned.
+fn signature_commitment(psbt_in: &psbt::Input) -> Option<(bool, Outputs)> {
+    let signed = psbt_in.tap_key_sig.is_some()
+        || !psbt_in.tap_script_sigs.is_empty()
+        || !psbt_in.partial_sigs.is_empty();
+    if !signed {
+        return None;
+    }
+    let sighash_type = psbt_in
+        .sighash_type
+        .and_then(|t| t.taproot_hash_ty().ok())
+        .or_else(|| psbt_in.tap_key_sig.map(|s| s.sighash_type))
+        .or_else(|| {
+            psbt_in
+                .tap_script_sigs
+                .values()
+                .next()
+                .map(|s| s.sighash_type)
+        })
+        .unwrap_or(TapSighashType::Default);
+    Some(match sighash_type {
+        TapSighashType::Default | TapSighashType::All => (false, Outputs::Fixed),
+        TapSighashType::None => (false, Outputs::Open),
+        TapSighashType::Single => (false, Outputs::Paired),
+        TapSighashType::AllPlusAnyoneCanPay => (true, Outputs::Fixed),
+        TapSighashType::NonePlusAnyoneCanPay => (true, Outputs::Open),
+        TapSighashType::SinglePlusAnyoneCanPay => (true, Outputs::Paired),
+    })
+}
+
+/// Which parts of a transaction can still change without invalidating the
+/// signatures already on it, from the sighash types those signatures use.
+///
+/// A `SIGHASH_SINGLE` signature only commits to the output paired with its
+/// input, so outputs may still be appended after it; the flag is set so
+/// constructors know to keep the pairing.
+fn signed_modifiable(psbt: &Psbt, mut flags: u8) -> u8 {
+    for psbt_in in &psbt.inputs {
+        let Some((anyone_can_pay, outputs)) = signature_commitment(psbt_in) else {
+            continue;
+        };
+        if !anyone_can_pay {
+            flags &= !INPUTS_MODIFIABLE;
+        }
+        match outputs {
+            Outputs::Fixed => flags &= !OUTPUTS_MODIFIABLE,
+            Outputs::Open => {}
+            Outputs::Paired => flags |= HAS_SIGHASH_SINGLE,
+        }
+    }
+    flags
+}
+
+/// Checks where the labitbu sats of a PSBT put together by
+/// [`PsbtV2::append`] go. Until the outputs are funded there is nothing to
+/// check yet.
+fn check_appended_sats(psbt: &Psbt) -> Result<(), Error> {
+    let locations = match first_sat_locations(psbt) {
+        Err(Error::SatFlowUnderflow) => return Ok(()),
+        result => result?,
+    };
+    for (input, psbt_in) in psbt.inputs.iter().enumerate() {
+        if is_listed_input(psbt_in) {
+            check_listed_input(psbt, input)?;
+        } else if !psbt_in.tap_scripts.is_empty()
+            && matches!(locations[input], SatLocation::Fee { .. })
+        {
+            return Err(Error::SatNotPreserved { input });
+        }
+    }
+    Ok(())
+}
+
+/// A PSBT in the BIP370 version 2 format, for transactions several parties
+/// build together: a marketplace buyer completing a listing, or depositors
+/// pooling into a batch mint.
+///
+/// Version 2 drops the global unsigned transaction in favour of per-input
+/// and per-output fields, and records whether inputs and outputs may still
+/// be added. Everything else in the crate works on [`Psbt`], so this wraps
+/// one and converts on the way in and out. Per-input locktime requirements
+/// are resolved into the transaction's locktime when read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PsbtV2 {
+    psbt: Psbt,
+    modifiable: u8,
+}
+
+impl PsbtV2 {
+    /// Wraps `psbt`, open to new inputs and outputs as far as its existing
+    /// signatures allow.
+    pub fn new(psbt: Psbt) -> Self {
+        let modifiable = signed_modifiable(&psbt, INPUTS_MODIFIABLE | OUTPUTS_MODIFIABLE);
+        PsbtV2 { psbt, modifiable }
+    }
+
+    pub fn psbt(&self) -> &Psbt {
+        &self.psbt
+    }
+
+    pub fn into_psbt(self) -> Psbt {
+        self.psbt
+    }
+
+    pub fn inputs_modifiable(&self) -> bool {
+        self.modifiable & INPUTS_MODIFIABLE != 0
+    }
+
+    pub fn outputs_modifiable(&self) -> bool {
+        self.modifiable & OUTPUTS_MODIFIABLE != 0
+    }
+
+    /// Whether some input is signed `SIGHASH_SINGLE`, so inputs and outputs
+    /// must stay paired by index.
+    pub fn has_sighash_single(&self) -> bool {
+        self.modifiable & HAS_SIGHASH_SINGLE != 0
+    }
+
+    /// Appends an input, which may already carry its own signatures.
+    pub fn add_input(&mut self, txin: TxIn, input: psbt::Input) -> Result<(), Error> {
+        if !self.inputs_modifiable() {
+            return Err(Error::PsbtV2("inputs are not modifiable"));
+        }
+        self.psbt.unsigned_tx.input.push(txin);
+        self.psbt.inputs.push(input);
+        self.modifiable = signed_modifiable(&self.psbt, self.modifiable);
+        Ok(())
+    }
+
+    /// Appends an output.
+    pub fn add_output(&mut self, txout: TxOut, output: psbt::Output) -> Result<(), Error> {
+        if !self.outputs_modifiable() {
+            return Err(Error::PsbtV2("outputs are not modifiable"));
+        }
+        self.psbt.unsigned_tx.output.push(txout);
+        self.psbt.outputs.push(output);
+        Ok(())
+    }
+
+    /// Appends every input and output of `contribution`, as when a buyer
+    /// adds a signed listing after their padding and then their funding.
+    ///
+    /// The contribution's inputs and outputs move up by the number already
+    /// here, so its signatures have to survive that: they must be
+    /// `ANYONECANPAY`, and a `SIGHASH_SINGLE` input only stays paired if this
+    /// PSBT has as many inputs as outputs. Once the result is funded, its
+    /// labitbu sats are checked: none may go to the fee, and an input tagged
+    /// by [`build_listing`] must start an output other than its payment.
+    ///
+    /// [`build_listing`]: crate::build_listing
+    pub fn append(&mut self, contribution: PsbtV2) -> Result<(), Error> {
+        if contribution.psbt.unsigned_tx.version != self.psbt.unsigned_tx.version {
+            return Err(Error::PsbtV2("transaction versions differ"));
+        }
+        let tx = &self.psbt.unsigned_tx;
+        for psbt_in in &contribution.psbt.inputs {
+            match signature_commitment(psbt_in) {
+                Some((false, _)) if !tx.input.is_empty() => {
+                    return Err(Error::PsbtV2("contribution is signed over every input"));
+                }
+                Some((_, Outputs::Fixed)) if !tx.output.is_empty() => {
+                    return Err(Error::PsbtV2("contribution is signed over every output"));
+                }
+                Some((_, Outputs::Paired)) if tx.input.len() != tx.output.len() => {
+                    return Err(Error::PsbtV2(
+                        "appending would break a SIGHASH_SINGLE pairing",
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        let mut combined = self.clone();
+        let PsbtV2 { psbt, .. } = contribution;
+        for (txin, input) in psbt.unsigned_tx.input.into_iter().zip(psbt.inputs) {
+            combined.add_input(txin, input)?;
+        }
+        for (txout, output) in psbt.unsigned_tx.output.into_iter().zip(psbt.outputs) {
+            combined.add_output(txout, output)?;
+        }
+        check_appended_sats(&combined.psbt)?;
+
+        *self = combined;
+        Ok(())
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let v0 = self.psbt.serialize();
+        let tx = &self.psbt.unsigned_tx;
+        let mut pos = MAGIC.len();
+        let malformed = "rust-bitcoin serializes well-formed PSBTs";
+
+        let mut global = read_map(&v0, &mut pos).expect(malformed);
+        global.retain(|(k, _)| !matches!(key_type(k), GLOBAL_UNSIGNED_TX | GLOBAL_VERSION));
+        let mut input_count = Vec::new();
+        write_compact_size(&mut input_count, tx.input.len() as u64);
+        let mut output_count = Vec::new();
+        write_compact_size(&mut output_count, tx.output.len() as u64);
+        global.extend([
+            (key(GLOBAL_TX_VERSION), tx.version.0.to_le_bytes().to_vec()),
+            (
+                key(GLOBAL_FALLBACK_LOCKTIME),
+                tx.lock_time.to_consensus_u32().to_le_bytes().to_vec(),
+            ),
+            (key(GLOBAL_INPUT_COUNT), input_count),
+            (key(GLOBAL_OUTPUT_COUNT), output_count),
+            (key(GLOBAL_TX_MODIFIABLE), vec![self.modifiable]),
+            (key(GLOBAL_VERSION), 2u32.to_le_bytes().to_vec()),
+        ]);
+
+        let mut out = MAGIC.to_vec();
+        write_map(&mut out, global);
+        for txin in &tx.input {
+            let mut map = read_map(&v0, &mut pos).expect(malformed);
+            map.extend([
+                (
+                    key(IN_PREVIOUS_TXID),
+                    txin.previous_output.txid.to_byte_array().to_vec(),
+                ),
+                (
+                    key(IN_OUTPUT_INDEX),
+                    txin.previous_output.vout.to_le_bytes().to_vec(),
+                ),
+                (
+                    key(IN_SEQUENCE),
+                    txin.sequence.to_consensus_u32().to_le_bytes().to_vec(),
+                ),
+            ]);
+            write_map(&mut out, map);
+        }
+        for txout in &tx.output {
+            let mut map = read_map(&v0, &mut pos).expect(malformed);
+            map.extend([
+                (
+                    key(OUT_AMOUNT),
+                    (txout.value.to_sat() as i64).to_le_bytes().to_vec(),
+                ),
+                (key(OUT_SCRIPT), txout.script_pubkey.to_bytes()),
+            ]);
+            write_map(&mut out, map);
+        }
+        out
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(Error::PsbtV2("not a PSBT"));
+        }
+        let mut pos = MAGIC.len();
+
+        let mut global = read_map(bytes, &mut pos)?;
+        if take_u32(&mut global, GLOBAL_VERSION)? != Some(2) {
+            return Err(Error::PsbtV2("not a version 2 PSBT"));
+        }
+        if take(&mut global, GLOBAL_UNSIGNED_TX)?.is_some() {
+            return Err(Error::PsbtV2(
+                "version 2 PSBTs have no unsigned transaction",
+            ));
+        }
+        let version = take_u32(&mut global, GLOBAL_TX_VERSION)?
+            .ok_or(Error::PsbtV2("missing transaction version"))?;
+        let fallback = take_u32(&mut global, GLOBAL_FALLBACK_LOCKTIME)?.unwrap_or(0);
+        let input_count = take_count(&mut global, GLOBAL_INPUT_COUNT)?;
+        let output_count = take_count(&mut global, GLOBAL_OUTPUT_COUNT)?;
+        let modifiable = match take(&mut global, GLOBAL_TX_MODIFIABLE)? {
+            None => 0,
+            Some(value) if value.len() == 1 => value[0],
+            Some(_) => return Err(Error::PsbtV2("tx modifiable is not 1 byte")),
+        };
+
+        let mut inputs = Vec::new();
+        let mut txins = Vec::new();
+        let mut required = Vec::new();
+        for _ in 0..input_count {
+            let mut map = read_map(bytes, &mut pos)?;
+            let txid = take(&mut map, IN_PREVIOUS_TXID)?
+                .and_then(|value| <[u8; 32]>::try_from(value).ok())
+                .ok_or(Error::PsbtV2("missing or malformed previous txid"))?;
+            let vout = take_u32(&mut map, IN_OUTPUT_INDEX)?
+                .ok_or(Error::PsbtV2("missing output index"))?;
+            let sequence = take_u32(&mut map, IN_SEQUENCE)?.unwrap_or(u32::MAX);
+            required.push((
+                take_u32(&mut map, IN_REQUIRED_TIME_LOCKTIME)?,
+                take_u32(&mut map, IN_REQUIRED_HEIGHT_LOCKTIME)?,
+            ));
+            txins.push(TxIn {
+                previous_output: OutPoint::new(Txid::from_byte_array(txid), vout),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence(sequence),
+                witness: Witness::new(),
+            });
+            inputs.push(map);
+        }
+
+        let mut outputs = Vec::new();
+        let mut txouts = Vec::new();
+        for _ in 0..output_count {
+            let mut map = read_map(bytes, &mut pos)?;
+            let amount = take(&mut map, OUT_AMOUNT)?
+                .and_then(|value| <[u8; 8]>::try_from(value).ok())
+                .map(i64::from_le_bytes)
+                .and_then(|sats| u64::try_from(sats).ok())
+                .ok_or(Error::PsbtV2("missing or malformed output amount"))?;
+            let script =
+                take(&mut map, OUT_SCRIPT)?.ok_or(Error::PsbtV2("missing output script"))?;
+            txouts.push(TxOut {
+                value: Amount::from_sat(amount),
+                script_pubkey: ScriptBuf::from_bytes(script),
+            });
+            outputs.push(map);
+        }
+        if pos != bytes.len() {
+            return Err(Error::PsbtV2("trailing data"));
+        }
+
+        let tx = Transaction {
+            version: transaction::Version(version as i32),
+            lock_time: determine_locktime(&required, fallback)?,
+            input: txins,
+            output: txouts,
+        };
+        global.push((key(GLOBAL_UNSIGNED_TX), serialize(&tx)));
+
+        let mut v0 = MAGIC.to_vec();
+        write_map(&mut v0, global);
+        for map in inputs.into_iter().chain(outputs) {
+            write_map(&mut v0, map);
+        }
+        Ok(PsbtV2 {
+            psbt: Psbt::deserialize(&v0)?,
+            modifiable,
+        })
+    }
+}
+
+/// Parses a PSBT in either the BIP174 or the BIP370 format.
+pub fn deserialize_psbt(bytes: &[u8]) -> Result<Psbt, Error> {
+    match PsbtV2::deserialize(bytes) {
+        Err(Error::PsbtV2("not a version 2 PSBT")) => Ok(Psbt::deserialize(bytes)?),
+        result => result.map(PsbtV2::into_psbt),
+    }
+}
+
+/// Converts a PSBT in either format to version 2.
+#[wasm_bindgen]
+pub fn psbt_to_v2(psbt_bytes: Vec<u8>) -> Result<Vec<u8>, JsValue> {
+    deserialize_psbt(&psbt_bytes)
+        .map(|psbt| PsbtV2::new(psbt).serialize())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Reads a PSBT in either format, for wallets that only take version 0.
+#[wasm_bindgen]
+pub fn psbt_from_v2(psbt_bytes: Vec<u8>) -> Result<PsbtResult, JsValue> {
+    deserialize_psbt(&psbt_bytes)
+        .map(Into::into)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Appends the inputs and outputs of `contribution` to `base`, both version
+/// 2 PSBTs, and returns the combined version 2 PSBT. See [`PsbtV2::append`]
+/// for what is refused.
+#[wasm_bindgen]
+pub fn append_psbt_v2(base: Vec<u8>, contribution: Vec<u8>) -> Result<Vec<u8>, JsValue> {
+    let mut base = PsbtV2::deserialize(&base).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let contribution =
+        PsbtV2::deserialize(&contribution).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    base.append(contribution)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(base.serialize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{Address, Network};
+    use secp256k1::{Keypair, Secp256k1};
+
+    use crate::{
+        add_labitbu_leaf, build_listing, create_taproot_spend_info, nums_from_tag, sign_psbt_with,
+        verify_taproot_spends, FundingInput, FundingKind,
+    };
+
+    fn keypair() -> Keypair {
+        Keypair::from_seckey_slice(&Secp256k1::new(), &[9u8; 32]).unwrap()
+    }
+
+    #[test]
+    fn mint_round_trips_through_version_2() {
+        let pubkey = keypair().x_only_public_key().0;
+        let spend_info = create_taproot_spend_info(pubkey, vec![5u8; 4096]).unwrap();
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+        };
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version(2),
+            lock_time: absolute::LockTime::from_height(840_000).unwrap(),
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 3),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(prevout);
+        add_labitbu_leaf(&mut psbt.inputs[0], pubkey, &spend_info);
+
+        let v2 = PsbtV2::new(psbt.clone());
+        assert!(v2.inputs_modifiable() && v2.outputs_modifiable());
+        let bytes = v2.serialize();
+
+        // No global unsigned transaction, and the version field says 2.
+        let mut global = read_map(&bytes, &mut MAGIC.len()).unwrap();
+        assert!(take(&mut global, GLOBAL_UNSIGNED_TX).unwrap().is_none());
+        assert_eq!(take_u32(&mut global, GLOBAL_VERSION).unwrap(), Some(2));
+
+        assert_eq!(PsbtV2::deserialize(&bytes).unwrap(), v2);
+        assert_eq!(deserialize_psbt(&bytes).unwrap(), psbt);
+        assert_eq!(deserialize_psbt(&psbt.serialize()).unwrap(), psbt);
+        assert!(PsbtV2::deserialize(&psbt.serialize()).is_err());
+    }
+
+    #[test]
+    fn buyer_appends_a_signed_listing_behind_padding() {
+        let seller = keypair();
+        let internal_key = seller.x_only_public_key().0;
+        let labitbu = FundingInput {
+            outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            prevout: TxOut {
+                value: Amount::from_sat(546),
+                script_pubkey: ScriptBuf::new_p2tr(&Secp256k1::new(), internal_key, None),
+            },
+            kind: FundingKind::P2trKeyPath { internal_key },
+        };
+        let payment = Address::p2tr(
+            &Secp256k1::new(),
+            nums_from_tag(b"seller"),
+            None,
+            Network::Bitcoin,
+        );
+        let mut listing =
+            build_listing(labitbu, Amount::from_sat(50_000), &payment.to_string()).unwrap();
+        sign_psbt_with(&mut listing, &seller).unwrap();
+
+        // SINGLE|ANYONECANPAY leaves room for the buyer, paired by index.
+        let listing = PsbtV2::deserialize(&PsbtV2::new(listing).serialize()).unwrap();
+        assert!(listing.inputs_modifiable() && listing.outputs_modifiable());
+        assert!(listing.has_sighash_single());
+
+        let buyer = Keypair::from_seckey_slice(&Secp256k1::new(), &[10u8; 32]).unwrap();
+        let buyer_key = buyer.x_only_public_key().0;
+        let buyer_script = ScriptBuf::new_p2tr(&Secp256k1::new(), buyer_key, None);
+        let buyer_psbt = |values: &[u64], outputs: &[u64]| {
+            let mut psbt = Psbt::from_unsigned_tx(Transaction {
+                version: transaction::Version(2),
+                lock_time: absolute::LockTime::ZERO,
+                input: (0..values.len())
+                    .map(|vout| TxIn {
+                        previous_output: OutPoint::new(Txid::all_zeros(), vout as u32 + 1),
+                        ..Default::default()
+                    })
+                    .collect(),
+                output: outputs
+                    .iter()
+                    .map(|value| TxOut {
+                        value: Amount::from_sat(*value),
+                        script_pubkey: buyer_script.clone(),
+                    })
+                    .collect(),
+            })
+            .unwrap();
+            for (psbt_in, value) in psbt.inputs.iter_mut().zip(values) {
+                psbt_in.witness_utxo = Some(TxOut {
+                    value: Amount::from_sat(*value),
+                    script_pubkey: buyer_script.clone(),
+                });
+                psbt_in.tap_internal_key = Some(buyer_key);
+            }
+            PsbtV2::new(psbt)
+        };
+
+        // Appended straight onto the listing, the labitbu would go back to
+        // the seller with the payment.
+        let mut unpadded = listing.clone();
+        assert!(matches!(
+            unpadded.append(buyer_psbt(&[60_000], &[9_000])),
+            Err(Error::SatNotPreserved { input: 0 })
+        ));
+        assert_eq!(unpadded, listing);
+
+        // Padding with a merged padding output and no receive output yet
+        // would pair the seller with the wrong output.
+        let mut unpaired = buyer_psbt(&[600, 600], &[1_200]);
+        assert!(matches!(
+            unpaired.append(listing.clone()),
+            Err(Error::PsbtV2(
+                "appending would break a SIGHASH_SINGLE pairing"
+            ))
+        ));
+
+        let mut purchase = buyer_psbt(&[600, 600], &[1_200, 546]);
+        purchase.append(listing).unwrap();
+        purchase.append(buyer_psbt(&[60_000], &[9_000])).unwrap();
+
+        // A sponsor's SINGLE|ANYONECANPAY fee input is not a listing, so it
+        // may go anywhere and need not sit behind padding.
+        let sponsor = Keypair::from_seckey_slice(&Secp256k1::new(), &[11u8; 32]).unwrap();
+        let mut sponsorship = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version(2),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 9),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: buyer_script.clone(),
+            }],
+        })
+        .unwrap();
+        sponsorship.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr(
+                &Secp256k1::new(),
+                sponsor.x_only_public_key().0,
+                None,
+            ),
+        });
+        sponsorship.inputs[0].tap_internal_key = Some(sponsor.x_only_public_key().0);
+        sponsorship.inputs[0].sighash_type = Some(TapSighashType::SinglePlusAnyoneCanPay.into());
+        sign_psbt_with(&mut sponsorship, &sponsor).unwrap();
+        let mut sponsored = purchase.clone();
+        sponsored.append(PsbtV2::new(sponsorship)).unwrap();
+        assert_eq!(sponsored.psbt().unsigned_tx.input.len(), 5);
+
+        let mut psbt = purchase.into_psbt();
+        assert_eq!(psbt.unsigned_tx.input.len(), 4);
+        assert_eq!(
+            psbt.unsigned_tx.output[2].script_pubkey,
+            payment.script_pubkey()
+        );
+        crate::check_listing_sat(&psbt, &buyer_script).unwrap();
+
+        // Once the buyer signs SIGHASH_ALL the seller's signature still
+        // holds, nothing more can be added, and the signed inputs cannot be
+        // moved into another transaction.
+        assert_eq!(sign_psbt_with(&mut psbt, &buyer).unwrap(), 3);
+        let mut tx = psbt.unsigned_tx.clone();
+        for (txin, psbt_in) in tx.input.iter_mut().zip(&psbt.inputs) {
+            txin.witness = Witness::from_slice(&[psbt_in.tap_key_sig.unwrap().to_vec()]);
+        }
+        let prevouts: Vec<TxOut> = psbt
+            .inputs
+            .iter()
+            .map(|psbt_in| psbt_in.witness_utxo.clone().unwrap())
+            .collect();
+        verify_taproot_spends(&tx, &prevouts).unwrap();
+
+        let mut signed = PsbtV2::new(psbt);
+        assert!(!signed.inputs_modifiable() && !signed.outputs_modifiable());
+        assert!(matches!(
+            signed.add_output(TxOut::NULL, psbt::Output::default()),
+            Err(Error::PsbtV2("outputs are not modifiable"))
+        ));
+        assert!(matches!(
+            buyer_psbt(&[600], &[]).append(signed),
+            Err(Error::PsbtV2("contribution is signed over every input"))
+        ));
+    }
+}
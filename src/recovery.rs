@@ -0,0 +1,249 @@
+use bitcoin::{
+    absolute, transaction, Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Transaction, TxIn, TxOut,
+    XOnlyPublicKey,
+};
+use serde::Deserialize;
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    add_labitbu_leaf, assert_sat_preserved, create_taproot_spend_info, labitbu_payload,
+    labitbu_payload_sleepy, parse_labitbu_key, parse_mainnet_address, predicted_vsize, Deposit,
+    Error, PsbtResult,
+};
+
+/// The trait images labitbus are rendered from, as loaded from the
+/// `labitbu-traits.json` and `labitbu-traits-sleepy.json` files.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct LabitbuAssets {
+    pub base_images: Vec<Vec<u8>>,
+    pub accessories: Vec<Vec<u8>>,
+    /// Accessories for the sleepy variant, which include the sleep mask.
+    #[serde(default)]
+    pub sleepy_accessories: Vec<Vec<u8>>,
+}
+
+/// A UTXO that may be sitting at one of a key's deposit addresses.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Utxo {
+    pub outpoint: OutPoint,
+    pub prevout: TxOut,
+}
+
+/// Regenerates every payload `pubkey` could have deposited for: the normal
+/// labitbu and the sleepy one.
+pub fn candidate_payloads(
+    pubkey: XOnlyPublicKey,
+    assets: &LabitbuAssets,
+) -> Result<Vec<Vec<u8>>, Error> {
+    let key = pubkey.serialize();
+    Ok(vec![
+        labitbu_payload(&key, &assets.base_images, &assets.accessories)?,
+        labitbu_payload_sleepy(&key, &assets.base_images, &assets.sleepy_accessories)?,
+    ])
+}
+
+/// Picks out the `candidates` paying to one of `pubkey`'s deposit addresses
+/// and pairs each with the payload that built it.
+pub fn find_deposits(
+    pubkey: XOnlyPublicKey,
+    assets: &LabitbuAssets,
+    candidates: Vec<Utxo>,
+) -> Result<Vec<Deposit>, Error> {
+    let scripts = candidate_payloads(pubkey, assets)?
+        .into_iter()
+        .map(|payload| {
+            let spend_info = create_taproot_spend_info(pubkey, payload.clone())?;
+            Ok((
+                ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+                payload,
+            ))
+        })
+        .collect::<Result<Vec<(ScriptBuf, Vec<u8>)>, Error>>()?;
+
+    Ok(candidates
+        .into_iter()
+        .filter_map(|utxo| {
+            scripts
+                .iter()
+                .find(|(script, _)| *script == utxo.prevout.script_pubkey)
+                .map(|(_, payload)| Deposit {
+                    pubkey,
+                    payload: payload.clone(),
+                    outpoint: utxo.outpoint,
+                    prevout: utxo.prevout,
+                })
+        })
+        .collect())
+}
+
+/// Builds a PSBT sweeping every deposit of `pubkey` found among `candidates`
+/// to `return_address`, paying `fee_rate` out of the deposits.
+///
+/// This is for deposits funded from a page whose state was lost: the payload
+/// is regenerated from the key, so nothing but the key and the trait images
+/// is needed to rebuild the leaf and spend it.
+pub fn build_recovery(
+    pubkey: XOnlyPublicKey,
+    assets: &LabitbuAssets,
+    candidates: Vec<Utxo>,
+    return_address: &str,
+    fee_rate: FeeRate,
+) -> Result<Psbt, Error> {
+    let deposits = find_deposits(pubkey, assets, candidates)?;
+    if deposits.is_empty() {
+        return Err(Error::NoDeposits);
+    }
+    let destination = parse_mainnet_address(return_address)?;
+    let available: Amount = deposits.iter().map(|d| d.prevout.value).sum();
+
+    let mut psbt = Psbt::from_unsigned_tx(Transaction {
+        version: transaction::Version(2),
+        lock_time: absolute::LockTime::ZERO,
+        input: deposits
+            .iter()
+            .map(|d| TxIn {
+                previous_output: d.outpoint,
+                ..Default::default()
+            })
+            .collect(),
+        output: vec![TxOut {
+            value: available,
+            script_pubkey: destination.script_pubkey(),
+        }],
+    })?;
+    for (psbt_in, d) in psbt.inputs.iter_mut().zip(deposits) {
+        let taproot_spend_info = create_taproot_spend_info(d.pubkey, d.payload)?;
+        psbt_in.witness_utxo = Some(d.prevout);
+        add_labitbu_leaf(psbt_in, d.pubkey, &taproot_spend_info);
+    }
+
+    let fee = fee_rate
+        .fee_vb(predicted_vsize(&psbt)?)
+        .ok_or(Error::FeeOverflow)?;
+    let dust = destination.script_pubkey().minimal_non_dust();
+    psbt.unsigned_tx.output[0].value = available
+        .checked_sub(fee)
+        .filter(|value| *value >= dust)
+        .ok_or(Error::InsufficientFunds {
+            available,
+            required: fee + dust,
+        })?;
+
+    assert_sat_preserved(&psbt, 0, 0)?;
+
+    Ok(psbt)
+}
+
+#[wasm_bindgen]
+pub fn recover_deposit(
+    pubkey_hex: &str,
+    assets: JsValue,
+    candidate_outpoints: JsValue,
+    return_address: String,
+    fee_rate_sat_vb: u64,
+) -> Result<PsbtResult, JsValue> {
+    let pubkey = parse_labitbu_key(pubkey_hex)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?
+        .pubkey;
+    let assets: LabitbuAssets = serde_wasm_bindgen::from_value(assets)
+        .map_err(|e| JsValue::from_str(&format!("assets: {}", e)))?;
+    let candidates: Vec<Utxo> = serde_wasm_bindgen::from_value(candidate_outpoints)
+        .map_err(|e| JsValue::from_str(&format!("candidate_outpoints: {}", e)))?;
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb)
+        .ok_or_else(|| JsValue::from_str("fee rate overflow"))?;
+
+    let psbt = build_recovery(pubkey, &assets, candidates, &return_address, fee_rate)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{hashes::Hash, Address, Network, Txid};
+    use image::{Rgba, RgbaImage};
+
+    use crate::{encode_to_webp_deterministic, nums_from_tag};
+
+    fn image(color: [u8; 4]) -> Vec<u8> {
+        encode_to_webp_deterministic(&RgbaImage::from_pixel(8, 8, Rgba(color))).unwrap()
+    }
+
+    fn assets() -> LabitbuAssets {
+        LabitbuAssets {
+            base_images: (0..4).map(|i| image([40 * i, 80, 120, 255])).collect(),
+            accessories: vec![image([0, 0, 0, 0])],
+            sleepy_accessories: vec![image([0, 0, 0, 0]); 3],
+        }
+    }
+
+    fn utxo(vout: u32, script_pubkey: ScriptBuf) -> Utxo {
+        Utxo {
+            outpoint: OutPoint::new(Txid::all_zeros(), vout),
+            prevout: TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey,
+            },
+        }
+    }
+
+    #[test]
+    fn recovers_normal_and_sleepy_deposits() {
+        let pubkey = nums_from_tag(b"depositor");
+        let assets = assets();
+        let payloads = candidate_payloads(pubkey, &assets).unwrap();
+        let script = |payload: &Vec<u8>| {
+            let spend_info = create_taproot_spend_info(pubkey, payload.clone()).unwrap();
+            ScriptBuf::new_p2tr_tweaked(spend_info.output_key())
+        };
+        let return_address = Address::p2tr(
+            &secp256k1::Secp256k1::verification_only(),
+            pubkey,
+            None,
+            Network::Bitcoin,
+        );
+
+        let candidates = vec![
+            utxo(0, script(&payloads[1])),
+            utxo(1, return_address.script_pubkey()),
+            utxo(2, script(&payloads[0])),
+        ];
+        let psbt = build_recovery(
+            pubkey,
+            &assets,
+            candidates,
+            &return_address.to_string(),
+            FeeRate::from_sat_per_vb_u32(2),
+        )
+        .unwrap();
+
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(tx.input[0].previous_output.vout, 0);
+        assert_eq!(tx.input[1].previous_output.vout, 2);
+        assert!(psbt.inputs.iter().all(|i| !i.tap_scripts.is_empty()));
+        assert_eq!(tx.output[0].script_pubkey, return_address.script_pubkey());
+        assert_eq!(
+            psbt.fee().unwrap(),
+            Amount::from_sat(2 * predicted_vsize(&psbt).unwrap())
+        );
+    }
+
+    #[test]
+    fn no_matching_candidates_is_an_error() {
+        let pubkey = nums_from_tag(b"depositor");
+        let candidates = vec![utxo(0, ScriptBuf::new_p2a())];
+
+        assert!(matches!(
+            build_recovery(
+                pubkey,
+                &assets(),
+                candidates,
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+                FeeRate::from_sat_per_vb_u32(2),
+            ),
+            Err(Error::NoDeposits)
+        ));
+    }
+}
@@ -0,0 +1,239 @@
+use bitcoin::{Psbt, TxOut};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::Error;
+
+/// A half-open range of sat numbers, `[start, end)`, as ord reports them.
+pub type SatRange = (u64, u64);
+
+/// Where a sat ends up after a transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SatLocation {
+    Output { vout: usize, offset: u64 },
+    Fee { offset: u64 },
+}
+
+/// The sat ranges each output of a transaction receives, plus the ranges paid
+/// to the miner as fee.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SatFlow {
+    pub outputs: Vec<Vec<SatRange>>,
+    pub fee: Vec<SatRange>,
+}
+
+impl SatFlow {
+    /// Finds which output, and how far into it, `sat` is assigned to.
+    pub fn locate(&self, sat: u64) -> Option<SatLocation> {
+        let find = |ranges: &[SatRange]| {
+            let mut offset = 0;
+            for &(start, end) in ranges {
+                if (start..end).contains(&sat) {
+                    return Some(offset + sat - start);
+                }
+                offset += end - start;
+            }
+            None
+        };
+
+        for (vout, ranges) in self.outputs.iter().enumerate() {
+            if let Some(offset) = find(ranges) {
+                return Some(SatLocation::Output { vout, offset });
+            }
+        }
+        find(&self.fee).map(|offset| SatLocation::Fee { offset })
+    }
+}
+
+/// Problems with where a labitbu sat ends up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SatWarning {
+    /// The sat is not in any input.
+    Missing { sat: u64 },
+    /// The sat would be paid to the miner.
+    LandsInFee { sat: u64 },
+    /// The sat would be sent to a change output.
+    LandsInChange { sat: u64, vout: usize },
+}
+
+/// Assigns input sat ranges to outputs first-in-first-out, the way ordinal
+/// theory tracks sats through a transaction. Whatever is left over after the
+/// last output is the fee.
+pub fn sat_flow(inputs: &[Vec<SatRange>], outputs: &[TxOut]) -> Result<SatFlow, Error> {
+    let mut pending = inputs.iter().flatten().copied().filter(|(s, e)| s < e);
+    let mut current: Option<SatRange> = pending.next();
+    let mut flow = SatFlow::default();
+
+    for output in outputs {
+        let mut ranges = Vec::new();
+        let mut remaining = output.value.to_sat();
+        while remaining > 0 {
+            let (start, end) = current.ok_or(Error::SatFlowUnderflow)?;
+            let take = remaining.min(end - start);
+            ranges.push((start, start + take));
+            remaining -= take;
+            current = if start + take == end {
+                pending.next()
+            } else {
+                Some((start + take, end))
+            };
+        }
+        flow.outputs.push(ranges);
+    }
+    flow.fee.extend(current);
+    flow.fee.extend(pending);
+
+    Ok(flow)
+}
+
+/// Flags every labitbu sat that would be lost to the fee or sent to one of the
+/// `change` outputs.
+pub fn check_labitbu_sats(flow: &SatFlow, sats: &[u64], change: &[usize]) -> Vec<SatWarning> {
+    sats.iter()
+        .filter_map(|&sat| match flow.locate(sat) {
+            None => Some(SatWarning::Missing { sat }),
+            Some(SatLocation::Fee { .. }) => Some(SatWarning::LandsInFee { sat }),
+            Some(SatLocation::Output { vout, .. }) if change.contains(&vout) => {
+                Some(SatWarning::LandsInChange { sat, vout })
+            }
+            Some(SatLocation::Output { .. }) => None,
+        })
+        .collect()
+}
+
+/// Numbers the sats of each input consecutively from zero, for when the real
+/// sat numbers are not known but relative positions are all that matter.
+pub(crate) fn relative_ranges(values: impl IntoIterator<Item = u64>) -> Vec<Vec<SatRange>> {
+    let mut next = 0;
+    values
+        .into_iter()
+        .map(|value| {
+            let range = (next, next + value);
+            next += value;
+            vec![range]
+        })
+        .collect()
+}
+
+/// Where the first sat of each input of `psbt` ends up.
+pub fn first_sat_locations(psbt: &Psbt) -> Result<Vec<SatLocation>, Error> {
+    let values = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            input
+                .witness_utxo
+                .as_ref()
+                .map(|o| o.value.to_sat())
+                .ok_or(Error::MissingUtxo(i))
+        })
+        .collect::<Result<Vec<u64>, Error>>()?;
+    let inputs = relative_ranges(values);
+    let flow = sat_flow(&inputs, &psbt.unsigned_tx.output)?;
+
+    Ok(inputs
+        .iter()
+        .map(|ranges| {
+            flow.locate(ranges[0].0)
+                .unwrap_or(SatLocation::Fee { offset: 0 })
+        })
+        .collect())
+}
+
+/// Checks that the first sat of input `input` becomes the first sat of output
+/// `vout`, which is where every builder in this crate puts a labitbu.
+pub fn assert_sat_preserved(psbt: &Psbt, input: usize, vout: usize) -> Result<(), Error> {
+    let locations = first_sat_locations(psbt)?;
+    match locations.get(input) {
+        Some(SatLocation::Output { vout: v, offset: 0 }) if *v == vout => Ok(()),
+        _ => Err(Error::SatNotPreserved { input }),
+    }
+}
+
+#[derive(Deserialize)]
+struct SatFlowRequest {
+    inputs: Vec<Vec<SatRange>>,
+    outputs: Vec<TxOut>,
+    #[serde(default)]
+    labitbu_sats: Vec<u64>,
+    #[serde(default)]
+    change: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct SatFlowReport {
+    flow: SatFlow,
+    warnings: Vec<SatWarning>,
+}
+
+#[wasm_bindgen(unchecked_return_type = "SatFlowReport")]
+pub fn trace_sats(request: JsValue) -> Result<JsValue, JsValue> {
+    let request: SatFlowRequest = serde_wasm_bindgen::from_value(request)
+        .map_err(|e| JsValue::from_str(&format!("request: {}", e)))?;
+
+    let flow = sat_flow(&request.inputs, &request.outputs)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let warnings = check_labitbu_sats(&flow, &request.labitbu_sats, &request.change);
+
+    serde_wasm_bindgen::to_value(&SatFlowReport { flow, warnings })
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{Amount, ScriptBuf};
+
+    fn output(value: u64) -> TxOut {
+        TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: ScriptBuf::new(),
+        }
+    }
+
+    #[test]
+    fn sats_flow_first_in_first_out() {
+        let inputs = vec![vec![(100, 110)], vec![(500, 505), (900, 920)]];
+        let outputs = vec![output(5), output(12), output(10)];
+
+        let flow = sat_flow(&inputs, &outputs).unwrap();
+
+        assert_eq!(flow.outputs[0], vec![(100, 105)]);
+        assert_eq!(flow.outputs[1], vec![(105, 110), (500, 505), (900, 902)]);
+        assert_eq!(flow.outputs[2], vec![(902, 912)]);
+        assert_eq!(flow.fee, vec![(912, 920)]);
+        assert_eq!(
+            flow.locate(501),
+            Some(SatLocation::Output { vout: 1, offset: 6 })
+        );
+    }
+
+    #[test]
+    fn labitbu_sats_in_fee_or_change_are_flagged() {
+        let inputs = vec![vec![(0, 10)], vec![(10, 20)], vec![(20, 30)]];
+        let outputs = vec![output(10), output(15)];
+
+        let flow = sat_flow(&inputs, &outputs).unwrap();
+        let warnings = check_labitbu_sats(&flow, &[0, 10, 27, 99], &[1]);
+
+        assert_eq!(
+            warnings,
+            vec![
+                SatWarning::LandsInChange { sat: 10, vout: 1 },
+                SatWarning::LandsInFee { sat: 27 },
+                SatWarning::Missing { sat: 99 },
+            ]
+        );
+    }
+
+    #[test]
+    fn outputs_larger_than_inputs_are_rejected() {
+        assert!(matches!(
+            sat_flow(&[vec![(0, 5)]], &[output(6)]),
+            Err(Error::SatFlowUnderflow)
+        ));
+    }
+}
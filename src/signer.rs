@@ -0,0 +1,401 @@
+use bitcoin::{
+    hashes::Hash,
+    key::TapTweak,
+    taproot::{self, TapLeafHash},
+    Psbt, XOnlyPublicKey,
+};
+use js_sys::Uint8Array;
+use secp256k1::{Keypair, Message, Secp256k1};
+use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
+
+use crate::{
+    deserialize_psbt, finalize_labitbu_inputs, parse_labitbu_key, spend_script, taproot_sighash,
+    Error,
+};
+
+/// Something that holds a taproot key and signs with it: a key in memory, a
+/// hardware wallet, an HSM or a remote service.
+///
+/// The builders only produce unsigned PSBTs, so any implementation can be
+/// handed to [`sign_psbt_with`] or [`sign_labitbu_input`] to sign what they
+/// build. Signatures are checked before they are added, so a faulty signer
+/// cannot slip an invalid witness into the PSBT.
+///
+/// [`sign_labitbu_input`]: crate::sign_labitbu_input
+pub trait Signer {
+    /// The x-only key this signer signs for.
+    fn get_xonly_pubkey(&self) -> Result<XOnlyPublicKey, Error>;
+
+    /// Signs input `index` of `psbt` spending the leaf `leaf_hash`, with the
+    /// sighash type recorded on the input.
+    fn sign_taproot_script_spend(
+        &self,
+        psbt: &Psbt,
+        index: usize,
+        leaf_hash: TapLeafHash,
+    ) -> Result<taproot::Signature, Error>;
+
+    /// Signs the BIP86 key-path spend of input `index` of `psbt`, which is
+    /// how a minted labitbu held at the key's own address is transferred or
+    /// listed.
+    fn sign_taproot_key_spend(
+        &self,
+        psbt: &Psbt,
+        index: usize,
+    ) -> Result<taproot::Signature, Error>;
+}
+
+/// Signs in memory with the keypair itself.
+impl Signer for Keypair {
+    fn get_xonly_pubkey(&self) -> Result<XOnlyPublicKey, Error> {
+        Ok(self.x_only_public_key().0)
+    }
+
+    fn sign_taproot_script_spend(
+        &self,
+        psbt: &Psbt,
+        index: usize,
+        leaf_hash: TapLeafHash,
+    ) -> Result<taproot::Signature, Error> {
+        let (sighash, sighash_type) = taproot_sighash(psbt, index, Some(leaf_hash))?;
+        let signature = Secp256k1::new().sign_schnorr_with_rng(
+            &Message::from_digest(sighash.to_byte_array()),
+            self,
+            &mut rand::thread_rng(),
+        );
+        Ok(taproot::Signature {
+            signature,
+            sighash_type,
+        })
+    }
+
+    fn sign_taproot_key_spend(
+        &self,
+        psbt: &Psbt,
+        index: usize,
+    ) -> Result<taproot::Signature, Error> {
+        let secp = Secp256k1::new();
+        let (sighash, sighash_type) = taproot_sighash(psbt, index, None)?;
+        let signature = secp.sign_schnorr_with_rng(
+            &Message::from_digest(sighash.to_byte_array()),
+            &self.tap_tweak(&secp, None).to_keypair(),
+            &mut rand::thread_rng(),
+        );
+        Ok(taproot::Signature {
+            signature,
+            sighash_type,
+        })
+    }
+}
+
+/// Signs by handing the whole PSBT to something outside the crate, such as a
+/// wallet or remote signer, and reading its signature back out of the PSBT
+/// it returns.
+///
+/// The external signer is asked once per input this crate wants signed.
+pub struct PsbtSigner<F> {
+    pubkey: XOnlyPublicKey,
+    sign: F,
+}
+
+impl<F> PsbtSigner<F>
+where
+    F: Fn(&Psbt) -> Result<Psbt, Error>,
+{
+    /// A signer for `pubkey` that calls `sign` with the PSBT to sign and
+    /// expects it back with `pubkey`'s signatures added.
+    pub fn new(pubkey: XOnlyPublicKey, sign: F) -> Self {
+        PsbtSigner { pubkey, sign }
+    }
+
+    fn round_trip(&self, psbt: &Psbt, index: usize) -> Result<Psbt, Error> {
+        let signed = (self.sign)(psbt)?;
+        if signed.unsigned_tx != psbt.unsigned_tx {
+            return Err(Error::Signer("signer changed the transaction"));
+        }
+        if signed.inputs.len() <= index {
+            return Err(Error::MissingSignature(index));
+        }
+        Ok(signed)
+    }
+}
+
+impl<F> Signer for PsbtSigner<F>
+where
+    F: Fn(&Psbt) -> Result<Psbt, Error>,
+{
+    fn get_xonly_pubkey(&self) -> Result<XOnlyPublicKey, Error> {
+        Ok(self.pubkey)
+    }
+
+    fn sign_taproot_script_spend(
+        &self,
+        psbt: &Psbt,
+        index: usize,
+        leaf_hash: TapLeafHash,
+    ) -> Result<taproot::Signature, Error> {
+        self.round_trip(psbt, index)?.inputs[index]
+            .tap_script_sigs
+            .get(&(self.pubkey, leaf_hash))
+            .copied()
+            .ok_or(Error::MissingSignature(index))
+    }
+
+    fn sign_taproot_key_spend(
+        &self,
+        psbt: &Psbt,
+        index: usize,
+    ) -> Result<taproot::Signature, Error> {
+        self.round_trip(psbt, index)?.inputs[index]
+            .tap_key_sig
+            .ok_or(Error::MissingSignature(index))
+    }
+}
+
+/// A signer living in JS, such as a browser wallet: a function taking the
+/// serialized PSBT and returning it, version 0 or 2, with its signatures.
+///
+/// It is called synchronously, once per input this crate wants signed. The
+/// builders that take one consume it, so make one per call.
+#[wasm_bindgen]
+pub struct JsSigner {
+    pubkey: XOnlyPublicKey,
+    sign: js_sys::Function,
+}
+
+#[wasm_bindgen]
+impl JsSigner {
+    /// A signer for `pubkey`, which may be in any format labitbu keys are
+    /// accepted in.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        pubkey: &str,
+        #[wasm_bindgen(unchecked_param_type = "(psbt: Uint8Array) => Uint8Array")]
+        sign: js_sys::Function,
+    ) -> Result<JsSigner, JsValue> {
+        let pubkey = parse_labitbu_key(pubkey)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?
+            .pubkey;
+        Ok(JsSigner { pubkey, sign })
+    }
+
+    fn call(&self, psbt: &Psbt) -> Result<Psbt, Error> {
+        let signed = self
+            .sign
+            .call1(&JsValue::NULL, &Uint8Array::from(&psbt.serialize()[..]))
+            .map_err(|_| Error::Signer("signer threw"))?;
+        if !signed.is_instance_of::<Uint8Array>() {
+            return Err(Error::Signer("signer did not return PSBT bytes"));
+        }
+        deserialize_psbt(&Uint8Array::from(signed).to_vec())
+    }
+
+    fn with_psbt_signer<T>(
+        &self,
+        f: impl FnOnce(&dyn Signer) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        f(&PsbtSigner::new(self.pubkey, |psbt: &Psbt| self.call(psbt)))
+    }
+}
+
+impl Signer for JsSigner {
+    fn get_xonly_pubkey(&self) -> Result<XOnlyPublicKey, Error> {
+        Ok(self.pubkey)
+    }
+
+    fn sign_taproot_script_spend(
+        &self,
+        psbt: &Psbt,
+        index: usize,
+        leaf_hash: TapLeafHash,
+    ) -> Result<taproot::Signature, Error> {
+        self.with_psbt_signer(|signer| signer.sign_taproot_script_spend(psbt, index, leaf_hash))
+    }
+
+    fn sign_taproot_key_spend(
+        &self,
+        psbt: &Psbt,
+        index: usize,
+    ) -> Result<taproot::Signature, Error> {
+        self.with_psbt_signer(|signer| signer.sign_taproot_key_spend(psbt, index))
+    }
+}
+
+/// Signs `psbt` with `signer` if the caller of a wasm builder handed one in,
+/// finalizing the labitbu inputs it signed. Refuses a signer that holds none
+/// of the keys, which is almost always the wrong wallet.
+pub(crate) fn sign_built_psbt(psbt: &mut Psbt, signer: Option<&JsSigner>) -> Result<(), Error> {
+    let Some(signer) = signer else {
+        return Ok(());
+    };
+    if sign_psbt_with(psbt, signer)? == 0 {
+        return Err(Error::Signer("signer holds none of the input keys"));
+    }
+    finalize_labitbu_inputs(psbt)
+}
+
+/// Checks a signature for input `index` against `key` before it goes into
+/// the PSBT: it has to use the sighash type the input asks for and verify.
+pub(crate) fn check_signature(
+    psbt: &Psbt,
+    index: usize,
+    leaf_hash: Option<TapLeafHash>,
+    key: XOnlyPublicKey,
+    signature: &taproot::Signature,
+) -> Result<(), Error> {
+    let (sighash, sighash_type) = taproot_sighash(psbt, index, leaf_hash)?;
+    if signature.sighash_type != sighash_type {
+        return Err(Error::SighashMismatch(index));
+    }
+    Secp256k1::verification_only()
+        .verify_schnorr(
+            &signature.signature,
+            &Message::from_digest(sighash.to_byte_array()),
+            &key,
+        )
+        .map_err(|_| Error::Signer("signature does not verify"))
+}
+
+/// Signs every input of `psbt` that `signer` holds the key for: labitbu
+/// leaves locked to its key, as in a mint, and BIP86 key-path inputs with its
+/// key as internal key, as in a transfer or listing. Returns how many inputs
+/// were signed.
+///
+/// Labitbu inputs still need [`finalize_labitbu_inputs`]; key-path inputs
+/// are finalized by whoever extracts the transaction.
+///
+/// [`finalize_labitbu_inputs`]: crate::finalize_labitbu_inputs
+pub fn sign_psbt_with(psbt: &mut Psbt, signer: &dyn Signer) -> Result<usize, Error> {
+    let secp = Secp256k1::verification_only();
+    let pubkey = signer.get_xonly_pubkey()?;
+    let leaf_script = spend_script(pubkey);
+    let (output_key, _) = pubkey.tap_tweak(&secp, None);
+
+    let mut signed = 0;
+    for index in 0..psbt.inputs.len() {
+        let psbt_in = &psbt.inputs[index];
+        if psbt_in
+            .tap_scripts
+            .values()
+            .any(|(script, _)| *script == leaf_script)
+        {
+            crate::sign_labitbu_input(psbt, index, signer)?;
+            signed += 1;
+        } else if psbt_in.tap_internal_key == Some(pubkey)
+            && psbt_in.tap_merkle_root.is_none()
+            && psbt_in.tap_scripts.is_empty()
+        {
+            let signature = signer.sign_taproot_key_spend(psbt, index)?;
+            check_signature(
+                psbt,
+                index,
+                None,
+                output_key.to_x_only_public_key(),
+                &signature,
+            )?;
+            psbt.inputs[index].tap_key_sig = Some(signature);
+            signed += 1;
+        }
+    }
+    Ok(signed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        absolute, transaction, Amount, OutPoint, ScriptBuf, Transaction, TxIn, TxOut, Txid, Witness,
+    };
+
+    use crate::{
+        add_labitbu_leaf, build_listing, create_taproot_spend_info, finalize_labitbu_inputs,
+        verify_finalized, verify_taproot_spends, FundingInput, FundingKind,
+    };
+
+    fn keypair() -> Keypair {
+        Keypair::from_seckey_slice(&Secp256k1::new(), &[7u8; 32]).unwrap()
+    }
+
+    #[test]
+    fn in_memory_signer_signs_a_listing_key_path() {
+        let keypair = keypair();
+        let internal_key = keypair.x_only_public_key().0;
+        let labitbu = FundingInput {
+            outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            prevout: TxOut {
+                value: Amount::from_sat(546),
+                script_pubkey: ScriptBuf::new_p2tr(&Secp256k1::new(), internal_key, None),
+            },
+            kind: FundingKind::P2trKeyPath { internal_key },
+        };
+        let payment = bitcoin::Address::p2tr(
+            &Secp256k1::new(),
+            crate::nums_from_tag(b"seller"),
+            None,
+            bitcoin::Network::Bitcoin,
+        );
+        let mut psbt =
+            build_listing(labitbu, Amount::from_sat(50_000), &payment.to_string()).unwrap();
+
+        assert_eq!(sign_psbt_with(&mut psbt, &keypair).unwrap(), 1);
+
+        let signature = psbt.inputs[0].tap_key_sig.unwrap();
+        assert_eq!(
+            signature.sighash_type,
+            bitcoin::TapSighashType::SinglePlusAnyoneCanPay
+        );
+        let mut tx = psbt.unsigned_tx.clone();
+        tx.input[0].witness = Witness::from_slice(&[signature.to_vec()]);
+        verify_taproot_spends(&tx, &[psbt.inputs[0].witness_utxo.clone().unwrap()]).unwrap();
+    }
+
+    #[test]
+    fn psbt_round_trip_signer_signs_a_mint() {
+        let keypair = keypair();
+        let pubkey = keypair.x_only_public_key().0;
+        let spend_info = create_taproot_spend_info(pubkey, vec![2u8; 4096]).unwrap();
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+        };
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version(2),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(prevout);
+        add_labitbu_leaf(&mut psbt.inputs[0], pubkey, &spend_info);
+
+        // Stands in for a remote signer: the PSBT only crosses as bytes.
+        let remote = |psbt: &Psbt| {
+            let mut theirs = Psbt::deserialize(&psbt.serialize()).unwrap();
+            crate::sign_labitbu_input(&mut theirs, 0, &keypair)?;
+            Ok(Psbt::deserialize(&theirs.serialize()).unwrap())
+        };
+        let mut lying = psbt.clone();
+        let tampering = PsbtSigner::new(pubkey, |psbt: &Psbt| {
+            let mut signed = remote(psbt)?;
+            signed.unsigned_tx.output[0].value = Amount::from_sat(1_000);
+            Ok(signed)
+        });
+        assert!(matches!(
+            sign_psbt_with(&mut lying, &tampering),
+            Err(Error::Signer("signer changed the transaction"))
+        ));
+
+        assert_eq!(
+            sign_psbt_with(&mut psbt, &PsbtSigner::new(pubkey, remote)).unwrap(),
+            1
+        );
+        finalize_labitbu_inputs(&mut psbt).unwrap();
+        verify_finalized(&psbt).unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+use bitcoin::{
+    psbt,
+    sighash::{Prevouts, SighashCache},
+    taproot::{self, ControlBlock, LeafVersion, TapLeafHash},
+    Psbt, ScriptBuf, TapSighash, TapSighashType, TxOut, Witness, XOnlyPublicKey,
+};
+use secp256k1::{schnorr, Secp256k1};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{deserialize_psbt, signer::check_signature, spend_script, Error, PsbtResult, Signer};
+
+/// Sets the sighash type a labitbu input will be signed with.
+///
+/// `ALL`, `NONE` and `SINGLE`, each with or without `ANYONECANPAY`, let other
+/// parties add inputs or outputs after the depositor has signed.
+pub fn set_sighash_type(
+    psbt: &mut Psbt,
+    index: usize,
+    sighash_type: TapSighashType,
+) -> Result<(), Error> {
+    let psbt_in = psbt
+        .inputs
+        .get_mut(index)
+        .ok_or(Error::NoSuchInput(index))?;
+    psbt_in.sighash_type = Some(sighash_type.into());
+    Ok(())
+}
+
+/// The sighash type an input asks for, `Default` if it does not say.
+fn input_sighash_type(psbt_in: &psbt::Input) -> Result<TapSighashType, Error> {
+    psbt_in
+        .sighash_type
+        .map(|t| t.taproot_hash_ty())
+        .transpose()
+        .map_err(|_| Error::InvalidSighashType)
+        .map(|t| t.unwrap_or(TapSighashType::Default))
+}
+
+/// The taproot sighash input `index` is signed over, for the script-path
+/// spend of `leaf_hash` or, without one, the key-path spend. Returns it with
+/// the sighash type recorded on the input.
+///
+/// With `ANYONECANPAY` only this input's prevout is committed to, so the
+/// other inputs do not need their UTXOs filled in yet.
+pub fn taproot_sighash(
+    psbt: &Psbt,
+    index: usize,
+    leaf_hash: Option<TapLeafHash>,
+) -> Result<(TapSighash, TapSighashType), Error> {
+    let psbt_in = psbt.inputs.get(index).ok_or(Error::NoSuchInput(index))?;
+    let sighash_type = input_sighash_type(psbt_in)?;
+
+    let anyone_can_pay = matches!(
+        sighash_type,
+        TapSighashType::AllPlusAnyoneCanPay
+            | TapSighashType::NonePlusAnyoneCanPay
+            | TapSighashType::SinglePlusAnyoneCanPay
+    );
+    let one_prevout;
+    let all_prevouts;
+    let prevouts = if anyone_can_pay {
+        one_prevout = psbt_in
+            .witness_utxo
+            .as_ref()
+            .ok_or(Error::MissingUtxo(index))?;
+        Prevouts::One(index, one_prevout)
+    } else {
+        all_prevouts = psbt
+            .inputs
+            .iter()
+            .enumerate()
+            .map(|(i, input)| input.witness_utxo.as_ref().ok_or(Error::MissingUtxo(i)))
+            .collect::<Result<Vec<&TxOut>, Error>>()?;
+        Prevouts::All(&all_prevouts)
+    };
+
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    let sighash = match leaf_hash {
+        Some(leaf_hash) => {
+            cache.taproot_script_spend_signature_hash(index, &prevouts, leaf_hash, sighash_type)?
+        }
+        None => cache.taproot_key_spend_signature_hash(index, &prevouts, sighash_type)?,
+    };
+
+    Ok((sighash, sighash_type))
+}
+
+/// The hash of the labitbu leaf input `index` spends.
+fn labitbu_leaf_hash(psbt: &Psbt, index: usize) -> Result<TapLeafHash, Error> {
+    let psbt_in = psbt.inputs.get(index).ok_or(Error::NoSuchInput(index))?;
+    let (_, (script, leaf_version)) = psbt_in
+        .tap_scripts
+        .iter()
+        .next()
+        .ok_or(Error::NotLabitbuInput(index))?;
+    Ok(TapLeafHash::from_script(script, *leaf_version))
+}
+
+/// The sighash the labitbu leaf of input `index` is signed over, along with
+/// the leaf hash and the sighash type recorded on the input.
+pub fn labitbu_sighash(
+    psbt: &Psbt,
+    index: usize,
+) -> Result<(TapSighash, TapLeafHash, TapSighashType), Error> {
+    let leaf_hash = labitbu_leaf_hash(psbt, index)?;
+    let (sighash, sighash_type) = taproot_sighash(psbt, index, Some(leaf_hash))?;
+    Ok((sighash, leaf_hash, sighash_type))
+}
+
+/// Records a signature over the labitbu leaf of input `index` that was made
+/// elsewhere, such as a MuSig2 aggregate signature.
+pub fn add_labitbu_signature(
+    psbt: &mut Psbt,
+    index: usize,
+    pubkey: XOnlyPublicKey,
+    signature: schnorr::Signature,
+) -> Result<(), Error> {
+    let (_, leaf_hash, sighash_type) = labitbu_sighash(psbt, index)?;
+    psbt.inputs[index].tap_script_sigs.insert(
+        (pubkey, leaf_hash),
+        taproot::Signature {
+            signature,
+            sighash_type,
+        },
+    );
+    Ok(())
+}
+
+/// Signs the labitbu leaf of input `index` with `signer`, using the sighash
+/// type recorded on the input. The leaf has to be locked to the signer's key.
+pub fn sign_labitbu_input(psbt: &mut Psbt, index: usize, signer: &dyn Signer) -> Result<(), Error> {
+    let pubkey = signer.get_xonly_pubkey()?;
+    let leaf_hash = labitbu_leaf_hash(psbt, index)?;
+    if leaf_hash != TapLeafHash::from_script(&spend_script(pubkey), LeafVersion::TapScript) {
+        return Err(Error::Signer("leaf is not locked to the signer's key"));
+    }
+    let signature = signer.sign_taproot_script_spend(psbt, index, leaf_hash)?;
+    check_signature(psbt, index, Some(leaf_hash), pubkey, &signature)?;
+
+    psbt.inputs[index]
+        .tap_script_sigs
+        .insert((pubkey, leaf_hash), signature);
+    Ok(())
+}
+
+/// Moves the signature on labitbu input `index` into its final witness:
+/// signature, leaf script, control block.
+///
+/// The signature has to carry the sighash type the input asked for, so a
+/// signer cannot silently widen or narrow what it committed to.
+pub fn finalize_labitbu_input(psbt: &mut Psbt, index: usize) -> Result<(), Error> {
+    let psbt_in = psbt
+        .inputs
+        .get_mut(index)
+        .ok_or(Error::NoSuchInput(index))?;
+    let sighash_type = input_sighash_type(psbt_in)?;
+    let (ctrl_block, (script, leaf_version)) = psbt_in
+        .tap_scripts
+        .iter()
+        .next()
+        .ok_or(Error::NotLabitbuInput(index))?;
+    if *leaf_version != LeafVersion::TapScript {
+        return Err(Error::NotLabitbuInput(index));
+    }
+    let leaf_hash = TapLeafHash::from_script(script, *leaf_version);
+
+    let signature = psbt_in
+        .tap_script_sigs
+        .iter()
+        .find(|((_, hash), _)| *hash == leaf_hash)
+        .map(|(_, sig)| *sig)
+        .ok_or(Error::MissingSignature(index))?;
+    if signature.sighash_type != sighash_type {
+        return Err(Error::SighashMismatch(index));
+    }
+
+    psbt_in.final_script_witness = Some(Witness::from_slice(&[
+        signature.to_vec(),
+        script.to_bytes(),
+        ctrl_block.serialize(),
+    ]));
+    psbt_in.sighash_type = None;
+    psbt_in.tap_internal_key = None;
+    psbt_in.tap_merkle_root = None;
+    psbt_in.tap_scripts.clear();
+    psbt_in.tap_script_sigs.clear();
+    psbt_in.tap_key_origins.clear();
+
+    Ok(())
+}
+
+/// Undoes [`finalize_labitbu_input`]: reads the leaf script and control block
+/// back out of a labitbu input's final witness and restores the fields
+/// needed to sign it again. Returns whether the input had such a witness.
+///
+/// The witness itself is left in place for the caller to clear.
+pub(crate) fn restore_labitbu_leaf(psbt_in: &mut psbt::Input) -> bool {
+    let (Some(witness), Some(prevout)) = (&psbt_in.final_script_witness, &psbt_in.witness_utxo)
+    else {
+        return false;
+    };
+    if witness.len() != 3 || !prevout.script_pubkey.is_p2tr() {
+        return false;
+    }
+    let Ok(signature) = taproot::Signature::from_slice(&witness[0]) else {
+        return false;
+    };
+    let script = ScriptBuf::from_bytes(witness[1].to_vec());
+    let Ok(ctrl_block) = ControlBlock::decode(&witness[2]) else {
+        return false;
+    };
+    let Ok(output_key) = XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..]) else {
+        return false;
+    };
+    let is_labitbu_leaf = script
+        .as_bytes()
+        .get(1..33)
+        .and_then(|key| XOnlyPublicKey::from_slice(key).ok())
+        .is_some_and(|key| spend_script(key) == script);
+    if !is_labitbu_leaf
+        || ctrl_block.leaf_version != LeafVersion::TapScript
+        || !ctrl_block.verify_taproot_commitment(
+            &Secp256k1::verification_only(),
+            output_key,
+            &script,
+        )
+    {
+        return false;
+    }
+
+    psbt_in.tap_internal_key = Some(ctrl_block.internal_key);
+    psbt_in.sighash_type = Some(signature.sighash_type.into());
+    psbt_in
+        .tap_scripts
+        .insert(ctrl_block, (script, LeafVersion::TapScript));
+    true
+}
+
+/// Finalizes every labitbu input that has been signed, leaving the rest for
+/// the wallet that owns them.
+pub fn finalize_labitbu_inputs(psbt: &mut Psbt) -> Result<(), Error> {
+    for index in 0..psbt.inputs.len() {
+        let psbt_in = &psbt.inputs[index];
+        if !psbt_in.tap_scripts.is_empty() && !psbt_in.tap_script_sigs.is_empty() {
+            finalize_labitbu_input(psbt, index)?;
+        }
+    }
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn set_mint_sighash(
+    psbt_bytes: Vec<u8>,
+    index: usize,
+    sighash_type: u8,
+) -> Result<PsbtResult, JsValue> {
+    let mut psbt = deserialize_psbt(&psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let sighash_type = TapSighashType::from_consensus_u8(sighash_type)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    set_sighash_type(&mut psbt, index, sighash_type)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+#[wasm_bindgen]
+pub fn finalize_mint(psbt_bytes: Vec<u8>) -> Result<PsbtResult, JsValue> {
+    let mut psbt = deserialize_psbt(&psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    finalize_labitbu_inputs(&mut psbt).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{
+        absolute, hashes::Hash, transaction, Amount, OutPoint, ScriptBuf, Transaction, TxIn, Txid,
+    };
+    use secp256k1::{Keypair, Secp256k1, SecretKey};
+
+    use crate::{add_labitbu_leaf, create_taproot_spend_info};
+
+    fn keypair() -> Keypair {
+        let sk = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        Keypair::from_secret_key(&Secp256k1::new(), &sk)
+    }
+
+    fn labitbu_psbt() -> Psbt {
+        let pubkey = keypair().x_only_public_key().0;
+        let spend_info = create_taproot_spend_info(pubkey, vec![9u8; 128]).unwrap();
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+        };
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version(2),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(prevout);
+        add_labitbu_leaf(&mut psbt.inputs[0], pubkey, &spend_info);
+        psbt
+    }
+
+    #[test]
+    fn sighash_type_is_carried_into_the_final_witness() {
+        let mut psbt = labitbu_psbt();
+        set_sighash_type(&mut psbt, 0, TapSighashType::SinglePlusAnyoneCanPay).unwrap();
+
+        sign_labitbu_input(&mut psbt, 0, &keypair()).unwrap();
+        finalize_labitbu_inputs(&mut psbt).unwrap();
+
+        let witness = psbt.inputs[0].final_script_witness.as_ref().unwrap();
+        assert_eq!(witness.len(), 3);
+        let sig = taproot::Signature::from_slice(&witness[0]).unwrap();
+        assert_eq!(sig.sighash_type, TapSighashType::SinglePlusAnyoneCanPay);
+        assert!(schnorr::Signature::from_slice(&witness[0][..64]).is_ok());
+        assert!(psbt.inputs[0].tap_scripts.is_empty());
+    }
+
+    #[test]
+    fn finalize_rejects_signature_with_other_sighash() {
+        let mut psbt = labitbu_psbt();
+        let stranger = Keypair::from_seckey_slice(&Secp256k1::new(), &[4u8; 32]).unwrap();
+        assert!(matches!(
+            sign_labitbu_input(&mut psbt, 0, &stranger),
+            Err(Error::Signer("leaf is not locked to the signer's key"))
+        ));
+
+        sign_labitbu_input(&mut psbt, 0, &keypair()).unwrap();
+        set_sighash_type(&mut psbt, 0, TapSighashType::All).unwrap();
+
+        assert!(matches!(
+            finalize_labitbu_input(&mut psbt, 0),
+            Err(Error::SighashMismatch(0))
+        ));
+    }
+}
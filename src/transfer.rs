@@ -0,0 +1,149 @@
+use bitcoin::{absolute, transaction, Amount, FeeRate, Psbt, Transaction, TxOut};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    add_funding_input, assert_sat_preserved, parse_mainnet_address, settle_change, sign_built_psbt,
+    Error, FundingInput, JsSigner, PsbtResult,
+};
+
+/// Builds a PSBT sending a labitbu to `recipient_address` without risking its
+/// sat.
+///
+/// The labitbu UTXO is input 0 and its whole value goes to output 0, so the
+/// labitbu sat is the first sat the recipient gets. `funding` pays the fee at
+/// `fee_rate` and change returns to the first funding input's script.
+pub fn build_transfer(
+    labitbu: FundingInput,
+    recipient_address: &str,
+    funding: Vec<FundingInput>,
+    fee_rate: FeeRate,
+) -> Result<Psbt, Error> {
+    let recipient = parse_mainnet_address(recipient_address)?;
+    let change_script = funding
+        .first()
+        .map(|f| f.prevout.script_pubkey.clone())
+        .ok_or(Error::NoFunding)?;
+
+    let mut psbt = Psbt::from_unsigned_tx(Transaction {
+        version: transaction::Version(2),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![],
+        output: vec![
+            TxOut {
+                value: labitbu.prevout.value,
+                script_pubkey: recipient.script_pubkey(),
+            },
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: change_script,
+            },
+        ],
+    })?;
+    add_funding_input(&mut psbt, labitbu)?;
+    for f in funding {
+        add_funding_input(&mut psbt, f)?;
+    }
+
+    settle_change(&mut psbt, fee_rate)?;
+    assert_sat_preserved(&psbt, 0, 0)?;
+
+    Ok(psbt)
+}
+
+#[wasm_bindgen]
+pub fn transfer(
+    labitbu: JsValue,
+    recipient_address: String,
+    funding: JsValue,
+    fee_rate_sat_vb: u64,
+    signer: Option<JsSigner>,
+) -> Result<PsbtResult, JsValue> {
+    let labitbu: FundingInput = serde_wasm_bindgen::from_value(labitbu)
+        .map_err(|e| JsValue::from_str(&format!("labitbu: {}", e)))?;
+    let funding: Vec<FundingInput> = serde_wasm_bindgen::from_value(funding)
+        .map_err(|e| JsValue::from_str(&format!("funding: {}", e)))?;
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb)
+        .ok_or_else(|| JsValue::from_str("fee rate overflow"))?;
+
+    let mut psbt = build_transfer(labitbu, &recipient_address, funding, fee_rate)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+    sign_built_psbt(&mut psbt, signer.as_ref()).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{hashes::Hash, Address, CompressedPublicKey, Network, OutPoint, ScriptBuf, Txid};
+    use secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+    use crate::{nums_from_tag, predicted_vsize, FundingKind};
+
+    fn wallet_pubkey() -> PublicKey {
+        let sk = SecretKey::from_slice(&[5u8; 32]).unwrap();
+        PublicKey::from_secret_key(&Secp256k1::new(), &sk)
+    }
+
+    fn p2wpkh(vout: u32, value: u64) -> FundingInput {
+        let pubkey = wallet_pubkey();
+        FundingInput {
+            outpoint: OutPoint::new(Txid::all_zeros(), vout),
+            prevout: TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new_p2wpkh(&CompressedPublicKey(pubkey).wpubkey_hash()),
+            },
+            kind: FundingKind::P2wpkh { pubkey },
+        }
+    }
+
+    #[test]
+    fn transfer_sends_labitbu_first_and_returns_change() {
+        let recipient = Address::p2tr(
+            &Secp256k1::verification_only(),
+            nums_from_tag(b"recipient"),
+            None,
+            Network::Bitcoin,
+        );
+
+        let psbt = build_transfer(
+            p2wpkh(0, 546),
+            &recipient.to_string(),
+            vec![p2wpkh(1, 10_000)],
+            FeeRate::from_sat_per_vb_u32(4),
+        )
+        .unwrap();
+
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.output[0].value, Amount::from_sat(546));
+        assert_eq!(tx.output[0].script_pubkey, recipient.script_pubkey());
+        assert_eq!(
+            tx.output[1].script_pubkey,
+            psbt.inputs[1].witness_utxo.as_ref().unwrap().script_pubkey
+        );
+        assert_eq!(
+            psbt.fee().unwrap(),
+            Amount::from_sat(4 * predicted_vsize(&psbt).unwrap())
+        );
+    }
+
+    #[test]
+    fn transfer_needs_funding_for_the_fee() {
+        let recipient = Address::p2tr(
+            &Secp256k1::verification_only(),
+            nums_from_tag(b"recipient"),
+            None,
+            Network::Bitcoin,
+        );
+
+        assert!(matches!(
+            build_transfer(
+                p2wpkh(0, 546),
+                &recipient.to_string(),
+                vec![p2wpkh(1, 100)],
+                FeeRate::from_sat_per_vb_u32(4),
+            ),
+            Err(Error::InsufficientFunds { .. })
+        ));
+    }
+}
@@ -0,0 +1,205 @@
+use bitcoin::{absolute, transaction, Amount, FeeRate, Psbt, ScriptBuf, Transaction, TxIn, TxOut};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    add_labitbu_leaf, assert_sat_preserved, build_cpfp_child, create_taproot_spend_info,
+    deserialize_psbt, parse_mainnet_address, weight::predicted_vsize, Deposit, Error, FundingInput,
+    FundingKind, PsbtResult,
+};
+
+/// Largest virtual size policy allows for a TRUC transaction.
+pub const TRUC_MAX_VSIZE: u64 = 10_000;
+/// Largest virtual size policy allows for a TRUC child of an unconfirmed parent.
+pub const TRUC_CHILD_MAX_VSIZE: u64 = 1_000;
+
+/// Builds a zero-fee v3 mint with an ephemeral pay-to-anchor output.
+///
+/// Output 0 receives the full deposited amount and output 1 is a zero-value
+/// P2A anchor. Relay policy only accepts the anchor while the mint pays no
+/// fee itself, so the whole fee comes from a child spending the anchor,
+/// which anyone can build with [`build_anchor_child`].
+pub fn build_truc_mint(deposits: Vec<Deposit>, destination_address: &str) -> Result<Psbt, Error> {
+    if deposits.is_empty() {
+        return Err(Error::NoDeposits);
+    }
+    let destination = parse_mainnet_address(destination_address)?;
+
+    let unsigned_tx = Transaction {
+        version: transaction::Version(3),
+        lock_time: absolute::LockTime::ZERO,
+        input: deposits
+            .iter()
+            .map(|d| TxIn {
+                previous_output: d.outpoint,
+                ..Default::default()
+            })
+            .collect(),
+        output: vec![
+            TxOut {
+                value: deposits.iter().map(|d| d.prevout.value).sum(),
+                script_pubkey: destination.script_pubkey(),
+            },
+            TxOut {
+                value: Amount::ZERO,
+                script_pubkey: ScriptBuf::new_p2a(),
+            },
+        ],
+    };
+    let mut psbt = Psbt::from_unsigned_tx(unsigned_tx)?;
+
+    for (psbt_in, d) in psbt.inputs.iter_mut().zip(deposits) {
+        let taproot_spend_info = create_taproot_spend_info(d.pubkey, d.payload)?;
+        psbt_in.witness_utxo = Some(d.prevout);
+        add_labitbu_leaf(psbt_in, d.pubkey, &taproot_spend_info);
+    }
+
+    assert_sat_preserved(&psbt, 0, 0)?;
+
+    let vsize = predicted_vsize(&psbt)?;
+    if vsize > TRUC_MAX_VSIZE {
+        return Err(Error::TrucTooLarge {
+            vsize,
+            max: TRUC_MAX_VSIZE,
+        });
+    }
+
+    Ok(psbt)
+}
+
+/// Builds the v3 child that spends a TRUC mint's anchor and pays the fee for
+/// both, bringing the package to `fee_rate`.
+///
+/// Funding inputs follow the anchor and everything left over goes to
+/// `change_address`.
+pub fn build_anchor_child(
+    parent: &Psbt,
+    funding: Vec<FundingInput>,
+    change_address: &str,
+    fee_rate: FeeRate,
+) -> Result<Psbt, Error> {
+    let anchor = parent
+        .unsigned_tx
+        .output
+        .iter()
+        .position(|o| o.script_pubkey == ScriptBuf::new_p2a())
+        .ok_or(Error::NoAnchor)?;
+
+    let mut child = build_cpfp_child(
+        parent,
+        anchor as u32,
+        FundingKind::PayToAnchor,
+        funding,
+        change_address,
+        fee_rate,
+    )?;
+    child.unsigned_tx.version = transaction::Version(3);
+
+    let vsize = predicted_vsize(&child)?;
+    if vsize > TRUC_CHILD_MAX_VSIZE {
+        return Err(Error::TrucTooLarge {
+            vsize,
+            max: TRUC_CHILD_MAX_VSIZE,
+        });
+    }
+
+    Ok(child)
+}
+
+#[wasm_bindgen]
+pub fn mint_truc(deposits: JsValue, destination_address: String) -> Result<PsbtResult, JsValue> {
+    let deposits: Vec<Deposit> = serde_wasm_bindgen::from_value(deposits)
+        .map_err(|e| JsValue::from_str(&format!("deposits: {}", e)))?;
+
+    let psbt = build_truc_mint(deposits, &destination_address)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(psbt.into())
+}
+
+#[wasm_bindgen]
+pub fn mint_anchor_child(
+    parent_psbt_bytes: Vec<u8>,
+    funding: JsValue,
+    change_address: String,
+    fee_rate_sat_vb: u64,
+) -> Result<PsbtResult, JsValue> {
+    let parent =
+        deserialize_psbt(&parent_psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let funding: Vec<FundingInput> = serde_wasm_bindgen::from_value(funding)
+        .map_err(|e| JsValue::from_str(&format!("funding: {}", e)))?;
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_vb)
+        .ok_or_else(|| JsValue::from_str("fee rate overflow"))?;
+
+    let child = build_anchor_child(&parent, funding, &change_address, fee_rate)
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    Ok(child.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{hashes::Hash, Address, Network, OutPoint, Txid};
+
+    use crate::{nums_from_tag, package_fee_rate};
+
+    fn wallet_address() -> String {
+        let secp = secp256k1::Secp256k1::verification_only();
+        Address::p2tr(&secp, nums_from_tag(b"wallet"), None, Network::Bitcoin).to_string()
+    }
+
+    fn deposit() -> Deposit {
+        let pubkey = nums_from_tag(b"depositor");
+        let payload = vec![1u8; 4096];
+        let spend_info = create_taproot_spend_info(pubkey, payload.clone()).unwrap();
+        Deposit {
+            pubkey,
+            payload,
+            outpoint: OutPoint::new(Txid::all_zeros(), 0),
+            prevout: TxOut {
+                value: Amount::from_sat(546),
+                script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+            },
+        }
+    }
+
+    #[test]
+    fn truc_mint_and_anchor_child_form_a_package() {
+        let parent = build_truc_mint(vec![deposit()], &wallet_address()).unwrap();
+        assert_eq!(parent.unsigned_tx.version, transaction::Version(3));
+        assert_eq!(parent.fee().unwrap(), Amount::ZERO);
+        assert_eq!(parent.unsigned_tx.output[1].value, Amount::ZERO);
+
+        let funding = FundingInput {
+            outpoint: OutPoint::new(Txid::all_zeros(), 1),
+            prevout: TxOut {
+                value: Amount::from_sat(50_000),
+                script_pubkey: ScriptBuf::new_p2tr(
+                    &secp256k1::Secp256k1::verification_only(),
+                    nums_from_tag(b"wallet"),
+                    None,
+                ),
+            },
+            kind: FundingKind::P2trKeyPath {
+                internal_key: nums_from_tag(b"wallet"),
+            },
+        };
+        let child = build_anchor_child(
+            &parent,
+            vec![funding],
+            &wallet_address(),
+            FeeRate::from_sat_per_vb_u32(3),
+        )
+        .unwrap();
+
+        assert_eq!(child.unsigned_tx.version, transaction::Version(3));
+        assert_eq!(child.unsigned_tx.input[0].previous_output.vout, 1);
+        let rate = package_fee_rate(
+            Amount::ZERO,
+            predicted_vsize(&parent).unwrap(),
+            child.fee().unwrap(),
+            predicted_vsize(&child).unwrap(),
+        );
+        assert_eq!(rate.to_sat_per_vb_floor(), 3);
+    }
+}
@@ -0,0 +1,301 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use bitcoin::{
+    bip32::{ChildNumber, KeySource, Xpub},
+    XOnlyPublicKey,
+};
+use secp256k1::{SecretKey, SECP256K1};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{
+    keys::parse_xpub_expression, labitbu_traits, labitbu_traits_sleepy, Error, LabitbuTraits,
+    ACCESSORY_NAMES, BASE_NAMES,
+};
+
+/// The look a vanity search is after. Unset fields match anything.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TraitFilter {
+    #[serde(default)]
+    pub base: Option<usize>,
+    #[serde(default)]
+    pub accessory: Option<usize>,
+    /// Only match labitbus without an accessory.
+    #[serde(default)]
+    pub bare: bool,
+    /// Inclusive hue shift range in degrees; wraps round if the start is
+    /// past the end.
+    #[serde(default)]
+    pub hue: Option<(u32, u32)>,
+    /// Predict the sleepy variant instead of the normal one.
+    #[serde(default)]
+    pub sleepy: bool,
+    /// How many base images and accessories generation will be given.
+    #[serde(default = "default_base_count")]
+    pub base_count: usize,
+    #[serde(default = "default_accessory_count")]
+    pub accessory_count: usize,
+}
+
+fn default_base_count() -> usize {
+    BASE_NAMES.len()
+}
+
+fn default_accessory_count() -> usize {
+    ACCESSORY_NAMES.len() - 1
+}
+
+impl TraitFilter {
+    /// The traits the labitbu for `pubkey` will have.
+    pub fn predict(&self, pubkey: &XOnlyPublicKey) -> LabitbuTraits {
+        if self.sleepy {
+            labitbu_traits_sleepy(&pubkey.serialize(), self.accessory_count)
+        } else {
+            labitbu_traits(&pubkey.serialize(), self.base_count, self.accessory_count)
+        }
+    }
+
+    pub fn matches(&self, traits: &LabitbuTraits) -> bool {
+        let hue_matches = match self.hue {
+            Some((start, end)) if start <= end => (start..=end).contains(&traits.hue_shift),
+            Some((start, end)) => traits.hue_shift >= start || traits.hue_shift <= end,
+            None => true,
+        };
+        self.base.is_none_or(|base| base == traits.base)
+            && self
+                .accessory
+                .is_none_or(|accessory| Some(accessory) == traits.accessory)
+            && !(self.bare && traits.accessory.is_some())
+            && hue_matches
+    }
+}
+
+/// A key whose labitbu passed the filter.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct VanityMatch {
+    pub pubkey: XOnlyPublicKey,
+    /// Where the key sits under the searched xpub.
+    pub origin: Option<KeySource>,
+    /// The key's secret, when it was generated at random.
+    pub secret_key: Option<SecretKey>,
+    pub traits: LabitbuTraits,
+}
+
+enum KeySpace {
+    Xpub {
+        xpub: Xpub,
+        origin: Option<KeySource>,
+        next: Option<u32>,
+    },
+    Random,
+}
+
+/// A resumable search for keys whose labitbu has a given look.
+///
+/// The search runs in batches through [`VanitySearch::step`], so a web worker
+/// can report progress between batches and stop when cancelled.
+#[wasm_bindgen]
+pub struct VanitySearch {
+    space: KeySpace,
+    filter: TraitFilter,
+    wanted: usize,
+    checked: u64,
+    found: Vec<VanityMatch>,
+    cancelled: Arc<AtomicBool>,
+    on_progress: Option<js_sys::Function>,
+}
+
+impl VanitySearch {
+    /// Searches the unhardened children of the xpub in `expression`, which
+    /// may carry an origin and path like any labitbu key expression.
+    pub fn over_xpub(expression: &str, filter: TraitFilter, wanted: usize) -> Result<Self, Error> {
+        let (xpub, origin) = parse_xpub_expression(expression.trim())?;
+        Ok(Self::new(
+            KeySpace::Xpub {
+                xpub,
+                origin,
+                next: Some(0),
+            },
+            filter,
+            wanted,
+        ))
+    }
+
+    /// Searches freshly generated random keys.
+    pub fn over_random_keys(filter: TraitFilter, wanted: usize) -> Self {
+        Self::new(KeySpace::Random, filter, wanted)
+    }
+
+    fn new(space: KeySpace, filter: TraitFilter, wanted: usize) -> Self {
+        VanitySearch {
+            space,
+            filter,
+            wanted,
+            checked: 0,
+            found: Vec::new(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            on_progress: None,
+        }
+    }
+
+    /// A flag that stops the search at the next key once set.
+    pub fn cancel_handle(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    pub fn matches(&self) -> &[VanityMatch] {
+        &self.found
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.found.len() >= self.wanted
+            || self.cancelled.load(Ordering::Relaxed)
+            || matches!(self.space, KeySpace::Xpub { next: None, .. })
+    }
+
+    /// Checks up to `batch` more keys. Returns whether the search is over.
+    pub fn step(&mut self, batch: u32) -> Result<bool, Error> {
+        for _ in 0..batch {
+            if self.is_done() {
+                break;
+            }
+            let (pubkey, origin, secret_key) = match &mut self.space {
+                KeySpace::Xpub { xpub, origin, next } => {
+                    let index = next.expect("search is not exhausted");
+                    let child = ChildNumber::from_normal_idx(index)?;
+                    *next = index.checked_add(1).filter(|i| *i < (1 << 31));
+                    let key = xpub.ckd_pub(SECP256K1, child)?;
+                    (
+                        key.to_x_only_pub(),
+                        origin
+                            .as_ref()
+                            .map(|(fingerprint, path)| (*fingerprint, path.child(child))),
+                        None,
+                    )
+                }
+                KeySpace::Random => {
+                    let secret_key = SecretKey::new(&mut rand::thread_rng());
+                    let pubkey = secret_key.x_only_public_key(SECP256K1).0;
+                    (pubkey, None, Some(secret_key))
+                }
+            };
+            self.checked += 1;
+
+            let traits = self.filter.predict(&pubkey);
+            if self.filter.matches(&traits) {
+                self.found.push(VanityMatch {
+                    pubkey,
+                    origin,
+                    secret_key,
+                    traits,
+                });
+            }
+        }
+        Ok(self.is_done())
+    }
+}
+
+#[wasm_bindgen]
+impl VanitySearch {
+    /// Starts a search over the children of `xpub`, or over random keys if
+    /// no xpub is given, for the first `wanted` keys passing `filter`.
+    #[wasm_bindgen(constructor)]
+    pub fn start(xpub: Option<String>, filter: JsValue, wanted: usize) -> Result<Self, JsValue> {
+        let filter: TraitFilter = serde_wasm_bindgen::from_value(filter)
+            .map_err(|e| JsValue::from_str(&format!("filter: {}", e)))?;
+
+        match xpub {
+            Some(xpub) => Self::over_xpub(&xpub, filter, wanted)
+                .map_err(|e| JsValue::from_str(&e.to_string())),
+            None => Ok(Self::over_random_keys(filter, wanted)),
+        }
+    }
+
+    /// Calls `callback(checked, found)` after every batch.
+    pub fn set_on_progress(&mut self, callback: js_sys::Function) {
+        self.on_progress = Some(callback);
+    }
+
+    /// Stops the search; the next `run_batch` returns straight away.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Checks up to `batch` more keys and reports progress. Returns whether
+    /// the search is over.
+    pub fn run_batch(&mut self, batch: u32) -> Result<bool, JsValue> {
+        let done = self
+            .step(batch)
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        if let Some(callback) = &self.on_progress {
+            callback.call2(
+                &JsValue::NULL,
+                &JsValue::from_f64(self.checked as f64),
+                &JsValue::from_f64(self.found.len() as f64),
+            )?;
+        }
+        Ok(done)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn checked(&self) -> u64 {
+        self.checked
+    }
+
+    #[wasm_bindgen(getter, js_name = matches, unchecked_return_type = "VanityMatch[]")]
+    pub fn matches_js(&self) -> Result<JsValue, JsValue> {
+        serde_wasm_bindgen::to_value(&self.found).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{bip32::Xpriv, Network};
+    use secp256k1::Secp256k1;
+
+    fn filter() -> TraitFilter {
+        TraitFilter {
+            base: Some(2),
+            accessory: Some(1),
+            bare: false,
+            hue: Some((300, 60)),
+            sleepy: false,
+            base_count: 4,
+            accessory_count: 2,
+        }
+    }
+
+    #[test]
+    fn xpub_search_finds_matching_children() {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Bitcoin, &[9u8; 32]).unwrap();
+        let xpub = Xpub::from_priv(&secp, &master);
+
+        let mut search = VanitySearch::over_xpub(&format!("{}/0", xpub), filter(), 2).unwrap();
+        while !search.step(100).unwrap() {}
+
+        assert_eq!(search.matches().len(), 2);
+        for m in search.matches() {
+            let (_, path) = m.origin.as_ref().unwrap();
+            let derived = xpub.derive_pub(&secp, path).unwrap();
+            assert_eq!(derived.to_x_only_pub(), m.pubkey);
+            assert_eq!(m.traits.base, 2);
+            assert_eq!(m.traits.accessory, Some(1));
+            assert!(m.traits.hue_shift >= 300 || m.traits.hue_shift <= 60);
+            assert_eq!(filter().predict(&m.pubkey), m.traits);
+        }
+    }
+
+    #[test]
+    fn cancelled_search_stops() {
+        let mut search = VanitySearch::over_random_keys(filter(), 1_000);
+        search.cancel_handle().store(true, Ordering::Relaxed);
+
+        assert!(search.step(10).unwrap());
+        assert_eq!(search.checked, 0);
+    }
+}
@@ -0,0 +1,338 @@
+use bitcoin::{
+    ecdsa,
+    hashes::Hash,
+    script::{Builder, PushBytesBuf},
+    sighash::{Prevouts, SighashCache},
+    taproot::{self, ControlBlock, LeafVersion, TapLeafHash},
+    CompressedPublicKey, Psbt, Script, ScriptBuf, Transaction, TxIn, TxOut, XOnlyPublicKey,
+};
+use secp256k1::{Message, Secp256k1};
+use wasm_bindgen::{prelude::wasm_bindgen, JsValue};
+
+use crate::{deserialize_psbt, spend_script, Error};
+
+/// Checks every input of `tx` against the output it spends, as a node would:
+/// taproot key path signatures against the output key, and for script paths
+/// the control block against the output key, then the leaf's signature.
+///
+/// Only single-key `<pubkey> OP_CHECKSIG` leaves are understood, which covers
+/// the labitbu leaf. Besides taproot, the P2WPKH and P2SH-P2WPKH inputs
+/// wallets fund mints with are checked; any other output type is refused.
+pub fn verify_taproot_spends(tx: &Transaction, prevouts: &[TxOut]) -> Result<(), Error> {
+    if prevouts.len() != tx.input.len() {
+        return Err(Error::PrevoutCount {
+            inputs: tx.input.len(),
+            prevouts: prevouts.len(),
+        });
+    }
+    let secp = Secp256k1::verification_only();
+    let mut cache = SighashCache::new(tx);
+
+    for (index, (txin, prevout)) in tx.input.iter().zip(prevouts).enumerate() {
+        let invalid = |reason| Error::InvalidWitness {
+            input: index,
+            reason,
+        };
+        if prevout.script_pubkey.is_p2wpkh() || prevout.script_pubkey.is_p2sh() {
+            verify_p2wpkh_spend(&mut cache, index, txin, prevout)?;
+            continue;
+        }
+        if !prevout.script_pubkey.is_p2tr() {
+            return Err(invalid("unsupported output type"));
+        }
+        if !txin.script_sig.is_empty() {
+            return Err(invalid("script sig must be empty"));
+        }
+        let output_key = XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..])
+            .map_err(|_| invalid("output key is not a valid point"))?;
+        let witness = &txin.witness;
+        if witness.is_empty() {
+            return Err(Error::MissingSignature(index));
+        }
+        if witness.taproot_annex().is_some() {
+            return Err(invalid("annex is not supported"));
+        }
+        let signature = taproot::Signature::from_slice(&witness[0])
+            .map_err(|_| invalid("malformed signature"))?;
+
+        let (key, sighash) = if witness.len() == 1 {
+            let sighash = cache.taproot_key_spend_signature_hash(
+                index,
+                &Prevouts::All(prevouts),
+                signature.sighash_type,
+            )?;
+            (output_key, sighash)
+        } else {
+            let script = witness
+                .taproot_leaf_script()
+                .ok_or(invalid("malformed control block"))?
+                .script;
+            let control_block = witness
+                .taproot_control_block()
+                .and_then(|c| ControlBlock::decode(c).ok())
+                .ok_or(invalid("malformed control block"))?;
+            if control_block.leaf_version != LeafVersion::TapScript {
+                return Err(invalid("unknown leaf version"));
+            }
+            if !control_block.verify_taproot_commitment(&secp, output_key, script) {
+                return Err(invalid("control block does not commit to the leaf"));
+            }
+            let key = checksig_key(script).ok_or(invalid("leaf is not a single-key checksig"))?;
+            if witness.len() != 3 {
+                return Err(invalid("leaf takes exactly one signature"));
+            }
+            let sighash = cache.taproot_script_spend_signature_hash(
+                index,
+                &Prevouts::All(prevouts),
+                TapLeafHash::from_script(script, LeafVersion::TapScript),
+                signature.sighash_type,
+            )?;
+            (key, sighash)
+        };
+
+        secp.verify_schnorr(
+            &signature.signature,
+            &Message::from_digest(sighash.to_byte_array()),
+            &key,
+        )
+        .map_err(|_| invalid("signature does not verify"))?;
+    }
+
+    Ok(())
+}
+
+/// Checks a P2WPKH spend, bare or nested in P2SH: the witness must be a
+/// signature and a compressed key hashing to the output's key hash.
+fn verify_p2wpkh_spend(
+    cache: &mut SighashCache<&Transaction>,
+    index: usize,
+    txin: &TxIn,
+    prevout: &TxOut,
+) -> Result<(), Error> {
+    let invalid = |reason| Error::InvalidWitness {
+        input: index,
+        reason,
+    };
+    let program = if prevout.script_pubkey.is_p2sh() {
+        let redeem_script = txin
+            .script_sig
+            .redeem_script()
+            .filter(|script| script.is_p2wpkh())
+            .ok_or(invalid("p2sh input is not nested p2wpkh"))?;
+        let push = PushBytesBuf::try_from(redeem_script.to_bytes())
+            .expect("a p2wpkh program fits in a push");
+        if Builder::new().push_slice(push).into_script() != txin.script_sig
+            || ScriptBuf::new_p2sh(&redeem_script.script_hash()) != prevout.script_pubkey
+        {
+            return Err(invalid("script sig does not match the p2sh output"));
+        }
+        redeem_script.to_owned()
+    } else {
+        if !txin.script_sig.is_empty() {
+            return Err(invalid("script sig must be empty"));
+        }
+        prevout.script_pubkey.clone()
+    };
+
+    let witness = &txin.witness;
+    if witness.is_empty() {
+        return Err(Error::MissingSignature(index));
+    }
+    if witness.len() != 2 {
+        return Err(invalid("p2wpkh witness must be a signature and a key"));
+    }
+    let signature =
+        ecdsa::Signature::from_slice(&witness[0]).map_err(|_| invalid("malformed signature"))?;
+    let pubkey = CompressedPublicKey::from_slice(&witness[1])
+        .map_err(|_| invalid("malformed public key"))?;
+    if ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()) != program {
+        return Err(invalid("key does not match the output"));
+    }
+
+    let sighash = cache
+        .p2wpkh_signature_hash(index, &program, prevout.value, signature.sighash_type)
+        .map_err(|_| invalid("cannot compute the signature hash"))?;
+    Secp256k1::verification_only()
+        .verify_ecdsa(
+            &Message::from_digest(sighash.to_byte_array()),
+            &signature.signature,
+            &pubkey.0,
+        )
+        .map_err(|_| invalid("signature does not verify"))
+}
+
+/// The key a `<pubkey> OP_CHECKSIG` leaf checks against.
+fn checksig_key(script: &Script) -> Option<XOnlyPublicKey> {
+    let bytes = script.as_bytes();
+    let key = XOnlyPublicKey::from_slice(bytes.get(1..33)?).ok()?;
+    (spend_script(key).as_script() == script).then_some(key)
+}
+
+/// Verifies every input of a finalized mint before it is broadcast.
+pub fn verify_finalized(psbt: &Psbt) -> Result<(), Error> {
+    let prevouts = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| input.witness_utxo.clone().ok_or(Error::MissingUtxo(i)))
+        .collect::<Result<Vec<TxOut>, Error>>()?;
+    let tx = psbt.clone().extract_tx_unchecked_fee_rate();
+
+    verify_taproot_spends(&tx, &prevouts)
+}
+
+#[wasm_bindgen]
+pub fn verify_mint(psbt_bytes: Vec<u8>) -> Result<(), JsValue> {
+    let psbt = deserialize_psbt(&psbt_bytes).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    verify_finalized(&psbt).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{absolute, transaction, Amount, OutPoint, ScriptBuf, TxIn, Txid, Witness};
+    use secp256k1::{Keypair, SecretKey};
+
+    use crate::{
+        add_labitbu_leaf, create_taproot_spend_info, finalize_labitbu_inputs, sign_labitbu_input,
+    };
+
+    fn keypair() -> Keypair {
+        Keypair::from_secret_key(
+            &Secp256k1::new(),
+            &SecretKey::from_slice(&[7u8; 32]).unwrap(),
+        )
+    }
+
+    fn unsigned_mint() -> Psbt {
+        let keypair = keypair();
+        let pubkey = keypair.x_only_public_key().0;
+        let spend_info = create_taproot_spend_info(pubkey, vec![1u8; 4096]).unwrap();
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+        };
+        let mut psbt = Psbt::from_unsigned_tx(Transaction {
+            version: transaction::Version(2),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(9_000),
+                script_pubkey: prevout.script_pubkey.clone(),
+            }],
+        })
+        .unwrap();
+        psbt.inputs[0].witness_utxo = Some(prevout);
+        add_labitbu_leaf(&mut psbt.inputs[0], pubkey, &spend_info);
+        psbt
+    }
+
+    fn signed_mint() -> Psbt {
+        let mut psbt = unsigned_mint();
+        sign_labitbu_input(&mut psbt, 0, &keypair()).unwrap();
+        finalize_labitbu_inputs(&mut psbt).unwrap();
+        psbt
+    }
+
+    #[test]
+    fn signed_mint_verifies() {
+        verify_finalized(&signed_mint()).unwrap();
+    }
+
+    #[test]
+    fn wallet_funding_inputs_must_be_signed() {
+        let secp = Secp256k1::new();
+        let wallet = SecretKey::from_slice(&[8u8; 32]).unwrap();
+        let wallet_key = CompressedPublicKey(wallet.public_key(&secp));
+        let program = ScriptBuf::new_p2wpkh(&wallet_key.wpubkey_hash());
+
+        for nested in [false, true] {
+            let mut psbt = unsigned_mint();
+            let funding = TxOut {
+                value: Amount::from_sat(5_000),
+                script_pubkey: if nested {
+                    ScriptBuf::new_p2sh(&program.script_hash())
+                } else {
+                    program.clone()
+                },
+            };
+            psbt.unsigned_tx.input.push(TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 1),
+                ..Default::default()
+            });
+            psbt.inputs.push(bitcoin::psbt::Input {
+                witness_utxo: Some(funding.clone()),
+                ..Default::default()
+            });
+            sign_labitbu_input(&mut psbt, 0, &keypair()).unwrap();
+            finalize_labitbu_inputs(&mut psbt).unwrap();
+            if nested {
+                psbt.inputs[1].final_script_sig = Some(
+                    Builder::new()
+                        .push_slice(PushBytesBuf::try_from(program.to_bytes()).unwrap())
+                        .into_script(),
+                );
+            }
+
+            assert!(matches!(
+                verify_finalized(&psbt),
+                Err(Error::MissingSignature(1))
+            ));
+
+            let sighash = SighashCache::new(&psbt.unsigned_tx)
+                .p2wpkh_signature_hash(1, &program, funding.value, bitcoin::EcdsaSighashType::All)
+                .unwrap();
+            let signature = ecdsa::Signature::sighash_all(
+                secp.sign_ecdsa(&Message::from_digest(sighash.to_byte_array()), &wallet),
+            );
+            psbt.inputs[1].final_script_witness = Some(Witness::from_slice(&[
+                signature.to_vec(),
+                wallet_key.to_bytes().to_vec(),
+            ]));
+            verify_finalized(&psbt).unwrap();
+
+            let tx = psbt.clone().extract_tx_unchecked_fee_rate();
+            assert!(matches!(
+                verify_taproot_spends(&tx, &[funding]),
+                Err(Error::PrevoutCount {
+                    inputs: 2,
+                    prevouts: 1
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn tampered_mint_is_rejected() {
+        let mut psbt = signed_mint();
+        psbt.unsigned_tx.output[0].value = Amount::from_sat(8_000);
+        assert!(matches!(
+            verify_finalized(&psbt),
+            Err(Error::InvalidWitness {
+                input: 0,
+                reason: "signature does not verify"
+            })
+        ));
+
+        let mut psbt = signed_mint();
+        let witness = psbt.inputs[0].final_script_witness.as_ref().unwrap();
+        let mut control_block = witness[2].to_vec();
+        control_block[40] ^= 1;
+        psbt.inputs[0].final_script_witness = Some(Witness::from_slice(&[
+            witness[0].to_vec(),
+            witness[1].to_vec(),
+            control_block,
+        ]));
+        assert!(matches!(
+            verify_finalized(&psbt),
+            Err(Error::InvalidWitness {
+                input: 0,
+                reason: "control block does not commit to the leaf"
+            })
+        ));
+    }
+}
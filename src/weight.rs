@@ -0,0 +1,81 @@
+use bitcoin::{
+    script::PushBytesBuf, Psbt, ScriptBuf, TapSighashType, Transaction, Weight, Witness,
+};
+
+use crate::Error;
+
+/// Size of a Schnorr signature with the default sighash.
+const SCHNORR_SIG_LEN: usize = 64;
+/// Largest DER-encoded ECDSA signature plus its sighash byte.
+const ECDSA_SIG_LEN: usize = 73;
+/// Size of a compressed public key.
+const COMPRESSED_PUBKEY_LEN: usize = 33;
+
+/// Returns `psbt`'s unsigned transaction with every input's scriptSig and
+/// witness filled in, either from its final fields or with placeholders of
+/// the size a signer will produce.
+pub fn with_predicted_witnesses(psbt: &Psbt) -> Result<Transaction, Error> {
+    let mut tx = psbt.unsigned_tx.clone();
+
+    for (txin, psbt_in) in tx.input.iter_mut().zip(&psbt.inputs) {
+        if let Some(witness) = &psbt_in.final_script_witness {
+            txin.witness = witness.clone();
+            txin.script_sig = psbt_in.final_script_sig.clone().unwrap_or_default();
+            continue;
+        }
+        if let Some(script_sig) = &psbt_in.final_script_sig {
+            txin.script_sig = script_sig.clone();
+            continue;
+        }
+
+        let prevout = psbt_in
+            .witness_utxo
+            .as_ref()
+            .ok_or(Error::UnsupportedInput(txin.previous_output))?;
+        let schnorr_sig_len = match psbt_in.sighash_type.map(|t| t.taproot_hash_ty()) {
+            Some(Ok(TapSighashType::Default)) | None => SCHNORR_SIG_LEN,
+            _ => SCHNORR_SIG_LEN + 1,
+        };
+
+        if let Some((ctrl_block, (script, _))) = psbt_in.tap_scripts.iter().next() {
+            txin.witness = Witness::from_slice(&[
+                vec![0u8; schnorr_sig_len],
+                script.to_bytes(),
+                ctrl_block.serialize(),
+            ]);
+        } else if prevout.script_pubkey.is_p2tr() {
+            txin.witness = Witness::from_slice(&[vec![0u8; schnorr_sig_len]]);
+        } else if prevout.script_pubkey.is_p2wpkh() {
+            txin.witness = p2wpkh_witness();
+        } else if prevout.script_pubkey.is_p2sh()
+            && psbt_in
+                .redeem_script
+                .as_ref()
+                .is_some_and(|s| s.is_p2wpkh())
+        {
+            let redeem_script = psbt_in.redeem_script.clone().unwrap();
+            let push = PushBytesBuf::try_from(redeem_script.into_bytes())
+                .expect("p2wpkh script fits in a push");
+            txin.script_sig = ScriptBuf::builder().push_slice(push).into_script();
+            txin.witness = p2wpkh_witness();
+        } else if prevout.script_pubkey != ScriptBuf::new_p2a() {
+            return Err(Error::UnsupportedInput(txin.previous_output));
+        }
+    }
+
+    Ok(tx)
+}
+
+fn p2wpkh_witness() -> Witness {
+    Witness::from_slice(&[vec![0u8; ECDSA_SIG_LEN], vec![0u8; COMPRESSED_PUBKEY_LEN]])
+}
+
+/// Predicts the weight of `psbt` once every input is signed.
+pub fn predicted_weight(psbt: &Psbt) -> Result<Weight, Error> {
+    Ok(with_predicted_witnesses(psbt)?.weight())
+}
+
+/// Predicts the virtual size of `psbt` once every input is signed.
+pub fn predicted_vsize(psbt: &Psbt) -> Result<u64, Error> {
+    Ok(predicted_weight(psbt)?.to_vbytes_ceil())
+}